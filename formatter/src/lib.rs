@@ -84,6 +84,26 @@ where
 pub struct DelayedFormat<'a> {
     format: &'a FormatString,
     map: &'a FormatMap,
+    escape: Option<fn(&str, &mut fmt::Formatter<'_>) -> fmt::Result>,
+}
+
+impl DelayedFormat<'_> {
+    /// Writes the values from the map through `escape` but the text of the format string as is,
+    /// for format strings that are markup
+    pub fn escape_values(self, escape: fn(&str, &mut fmt::Formatter<'_>) -> fmt::Result) -> Self {
+        Self {
+            escape: Some(escape),
+            ..self
+        }
+    }
+}
+
+struct Formatted<'a>(&'a MapCont, &'a FormatOptions);
+
+impl fmt::Display for Formatted<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.format_with(self.1, fmt)
+    }
 }
 
 impl std::fmt::Display for DelayedFormat<'_> {
@@ -95,7 +115,10 @@ impl std::fmt::Display for DelayedFormat<'_> {
                 }
                 Op::FromMap { key, fmt_opt } => {
                     let cont = self.map.get(key).unwrap();
-                    cont.format_with(fmt_opt, fmt)?;
+                    match self.escape {
+                        Some(escape) => escape(&Formatted(cont, fmt_opt).to_string(), fmt)?,
+                        None => cont.format_with(fmt_opt, fmt)?,
+                    }
                 }
             }
         }
@@ -120,7 +143,11 @@ impl FormatString {
         {
             Err(Error::KeyNotInMap(key.to_owned()))
         } else {
-            Ok(DelayedFormat { format: &self, map })
+            Ok(DelayedFormat {
+                format: &self,
+                map,
+                escape: None,
+            })
         }
     }
 
@@ -297,6 +324,7 @@ macro_rules! unitize {
 unitize!(1024., unitize_bin);
 unitize!(1000., unitize_si);
 
+#[derive(Clone)]
 pub enum MapCont {
     Number(R64),
     Str(String),
@@ -327,7 +355,7 @@ impl From<Duration> for MapCont {
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct FormatMap(HashMap<String, MapCont>);
 
 impl FormatMap {
@@ -364,4 +392,20 @@ impl FormatMap {
     fn get(&self, key: &str) -> Option<&MapCont> {
         self.0.get(key)
     }
+
+    /// Numbers and durations in seconds
+    pub fn get_number(&self, key: &str) -> Option<f64> {
+        match self.get(key)? {
+            MapCont::Number(n) => Some(n.raw()),
+            MapCont::Duration(duration) => Some(duration.as_secs_f64()),
+            MapCont::Str(_) => None,
+        }
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        match self.get(key)? {
+            MapCont::Str(s) => Some(s),
+            _ => None,
+        }
+    }
 }
@@ -48,3 +48,15 @@ fn format() {
     let fmt = FormatString::parse("{test:.2} this").unwrap();
     assert_eq!("-20.00 this", &fmt.fmt(&map).unwrap().to_string());
 }
+
+#[test]
+fn escape_values() {
+    let fmt = FormatString::parse("<b>{title}</b> {{}}").unwrap();
+    let mut map = FormatMap::new();
+    map.insert("title", "Rock & Roll".to_owned());
+    let escaped = fmt
+        .fmt(&map)
+        .unwrap()
+        .escape_values(|s, f| f.write_str(&s.replace('&', "&amp;")));
+    assert_eq!("<b>Rock &amp; Roll</b> {}", &escaped.to_string());
+}
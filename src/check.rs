@@ -0,0 +1,373 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use failure::format_err;
+use formatter::FormatString;
+use toml::Value;
+
+use crate::{
+    config::{
+        self,
+        include::{self, Merged},
+        locate,
+        path::{KeyPath, Step},
+        Config,
+    },
+    statusbar::{AlertCfg, Limit},
+    widget::{self, WidgetCfg, WidgetKind},
+};
+
+/// Something wrong in the config
+#[derive(Debug)]
+pub struct Problem {
+    // e.g. `widgets[1].alerts[0].message`
+    path: KeyPath,
+    // only set if the config is made of several files
+    file: Option<PathBuf>,
+    // zero based
+    pos: Option<(usize, usize)>,
+    error: failure::Error,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.0.is_empty() {
+            f.write_str("config")?;
+        } else {
+            write!(f, "{}", self.path)?;
+        }
+        if let Some(ref file) = self.file {
+            write!(f, " in {}", file.display())?;
+        }
+        if let Some((line, col)) = self.pos {
+            write!(f, " at line {} column {}", line + 1, col + 1)?;
+        }
+        write!(f, ": {}", self.error)
+    }
+}
+
+/// Validates the config at `path` and builds the widgets that work without hardware or a server
+pub fn run(path: &Path) -> Result<(), failure::Error> {
+    let merged = include::load(path)?;
+    let problems = check(&merged);
+    for problem in &problems {
+        eprintln!("{}", problem);
+    }
+    let unknown_keys = config::unknown_keys(&merged.table);
+    for key in &unknown_keys {
+        eprintln!("{}: unknown key", key);
+    }
+    let count = problems.len() + unknown_keys.len();
+    if count == 0 {
+        println!("{} is fine", path.display());
+        Ok(())
+    } else {
+        Err(format_err!(
+            "Found {} problems in {}",
+            count,
+            path.display()
+        ))
+    }
+}
+
+/// Widgets are checked one by one so a broken widget doesn't hide the problems of the others
+pub fn check(merged: &Merged) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let mut report = |path: KeyPath, error| {
+        let found = position(merged, &path);
+        problems.push(Problem {
+            file: found
+                .filter(|_| merged.layers.len() > 1)
+                .map(|(layer, _)| merged.layers[layer].path.clone()),
+            pos: found.map(|(_, pos)| pos),
+            path,
+            error,
+        })
+    };
+
+    let mut rest = merged.table.clone();
+    let widgets = match rest.remove("widgets") {
+        Some(Value::Array(widgets)) => widgets,
+        Some(_) => {
+            report(
+                KeyPath(vec![Step::Key("widgets".to_owned())]),
+                format_err!("Needs to be an array of widgets"),
+            );
+            Vec::new()
+        }
+        None => Vec::new(),
+    };
+    let rest = Value::Table(rest);
+    if let Err(e) = rest.clone().try_into::<Config>() {
+        report(locate::culprit::<Config>(&rest), e.into());
+    }
+    for (i, widget) in widgets.into_iter().enumerate() {
+        let mut report = |path: KeyPath, error| {
+            let mut full = KeyPath(vec![Step::Key("widgets".to_owned()), Step::Index(i)]);
+            full.0.extend(path.0);
+            report(full, error)
+        };
+        match widget.clone().try_into::<WidgetCfg>() {
+            Ok(widget) => {
+                // these read sysfs, sensors or alsa or connect to mpd as soon as they're built
+                let hardware = matches!(
+                    widget.kind,
+                    WidgetKind::Battery(_)
+                        | WidgetKind::Temp(_)
+                        | WidgetKind::Volume(_)
+                        | WidgetKind::Mpd(_)
+                );
+                if !check_widget(&widget, &mut report) && !hardware {
+                    if let Err(e) = widget::widget_from_kind(widget.kind) {
+                        report(KeyPath::default(), e);
+                    }
+                }
+            }
+            Err(e) => report(locate::culprit::<WidgetCfg>(&widget), e.into()),
+        }
+    }
+    problems
+}
+
+/// Reports paths relative to the widget, returns whether it found anything
+fn check_widget(widget: &WidgetCfg, report: &mut dyn FnMut(KeyPath, failure::Error)) -> bool {
+    let mut broken = false;
+    let mut report = |path, error| {
+        broken = true;
+        report(path, error)
+    };
+    let field = |name: &str| KeyPath(vec![Step::Key(name.to_owned())]);
+    let alert = |j: usize, names: &[&str]| {
+        let mut path = KeyPath(vec![Step::Key("alerts".to_owned()), Step::Index(j)]);
+        path.0
+            .extend(names.iter().map(|name| Step::Key((*name).to_owned())));
+        path
+    };
+
+    let formats = widget.kind.formats();
+    for &(name, format, keys) in &formats {
+        if let Err(e) = FormatString::parse_with_allowed_keys(format, keys) {
+            report(field(name), e.into());
+        }
+    }
+    if let Some(ref format) = widget.format_error {
+        if let Err(e) = FormatString::parse_with_allowed_keys(format, &["error"]) {
+            report(field("format_error"), e.into());
+        }
+    }
+    if let WidgetKind::Datetime(ref cfg) = widget.kind {
+        if let Err(e) = cfg.check_format() {
+            report(field("format"), e);
+        }
+    }
+
+    // alerts can use every key the widget computes
+    let keys = formats
+        .iter()
+        .flat_map(|&(_, _, keys)| keys.iter().cloned())
+        .filter(|key| !key.is_empty())
+        .collect::<Vec<_>>();
+    let unknown = |key: &str| {
+        format_err!(
+            "Unknown key {}, {} has {}",
+            key,
+            widget.kind.name(),
+            keys.join(", ")
+        )
+    };
+    for (j, alert_cfg) in widget.alerts.iter().enumerate() {
+        let AlertCfg {
+            ref key,
+            ref above,
+            ref below,
+            ref when,
+            ref message,
+            ..
+        } = *alert_cfg;
+        if !keys.contains(&key.as_str()) {
+            report(alert(j, &["key"]), unknown(key));
+        }
+        if above.is_none() && below.is_none() {
+            report(
+                alert(j, &[]),
+                format_err!("Needs a limit in `above` or `below`"),
+            );
+        }
+        for (name, limit) in [("above", above), ("below", below)] {
+            if let Some(Limit::Key(ref key)) = *limit {
+                if !keys.contains(&key.as_str()) {
+                    report(alert(j, &[name]), unknown(key));
+                }
+            }
+        }
+        for key in when.keys().filter(|key| !keys.contains(&key.as_str())) {
+            report(alert(j, &["when", key]), unknown(key));
+        }
+        if let Err(e) = FormatString::parse_with_allowed_keys(message, &keys) {
+            report(alert(j, &["message"]), e.into());
+        }
+    }
+
+    broken
+}
+
+/// Layer and position of the value at `path`. A widget can be spread over several files, the one
+/// that has most of `path` wins.
+fn position(merged: &Merged, path: &KeyPath) -> Option<(usize, (usize, usize))> {
+    let candidates = match path.0.as_slice() {
+        [Step::Key(key), Step::Index(i), rest @ ..] if key == "widgets" => merged
+            .origins
+            .get(*i)?
+            .iter()
+            .map(|&(layer, j)| {
+                let mut path = KeyPath(vec![Step::Key(key.clone()), Step::Index(j)]);
+                path.0.extend_from_slice(rest);
+                (layer, path)
+            })
+            .collect(),
+        _ => (0..merged.layers.len())
+            .map(|layer| (layer, path.clone()))
+            .collect::<Vec<_>>(),
+    };
+    candidates
+        .into_iter()
+        .filter_map(|(layer, path)| {
+            let (found, pos) = locate::locate(&merged.layers[layer].src, &path)?;
+            Some((found, layer, pos))
+        })
+        .filter(|&(found, ..)| found > 0)
+        .max_by_key(|&(found, ..)| found)
+        .map(|(_, layer, pos)| (layer, pos))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    // writes `files` to a fresh directory and checks the first one
+    fn check_files(name: &str, files: &[(&str, &str)]) -> (PathBuf, Vec<Problem>) {
+        let dir =
+            std::env::temp_dir().join(format!("statusbar-check-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (file, cont) in files {
+            fs::write(dir.join(file), cont).unwrap();
+        }
+        let dir = fs::canonicalize(&dir).unwrap();
+        let problems = check(&include::load(&dir.join(files[0].0)).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+        (dir, problems)
+    }
+
+    #[test]
+    fn problems_have_positions() {
+        let src = format!(
+            "{}{}",
+            Config::default().0,
+            r#"
+[[widgets]]
+type = "memory"
+format = "{used} {swap}"
+
+[[widgets.alerts]]
+key = "percent_used"
+above = 90
+message = "memory"
+
+[[widgets.alerts]]
+key = "percent_free"
+below = "full"
+message = "{free}"
+
+[[widgets.alerts]]
+key = "percent_used"
+message = "no limit"
+
+[[widgets]]
+type = "datetime"
+format = "%Y %Q"
+"#
+        );
+        let (_, problems) = check_files("positions", &[("config.toml", &src)]);
+        let index = Config::default().1.widgets.len();
+        let line = src
+            .lines()
+            .position(|line| line.starts_with("format = \"{used}"))
+            .unwrap();
+        let path = |problem: &Problem| problem.path.to_string();
+
+        assert_eq!(problems.len(), 5);
+        assert_eq!(path(&problems[0]), format!("widgets[{}].format", index));
+        assert_eq!(problems[0].file, None);
+        assert_eq!(problems[0].pos, Some((line, 0)));
+        assert_eq!(
+            path(&problems[1]),
+            format!("widgets[{}].alerts[1].key", index)
+        );
+        assert_eq!(problems[1].pos, Some((line + 8, 0)));
+        assert_eq!(
+            path(&problems[2]),
+            format!("widgets[{}].alerts[1].message", index)
+        );
+        assert_eq!(path(&problems[3]), format!("widgets[{}].alerts[2]", index));
+        assert_eq!(problems[3].pos, Some((line + 12, 0)));
+        assert_eq!(path(&problems[4]), format!("widgets[{}].format", index + 1));
+    }
+
+    #[test]
+    fn widgets_get_checked_one_by_one() {
+        let (dir, problems) = check_files(
+            "includes",
+            &[
+                (
+                    "config.toml",
+                    r#"include = ["base.toml"]
+
+[[widgets]]
+type = "datetime"
+format = "%Q"
+
+[[widgets]]
+type = "cpu_usage"
+"#,
+                ),
+                (
+                    "base.toml",
+                    r#"widgets = [
+    { type = "battery", bat_name = 0 },
+    { type = "memory", interval = "often" },
+]
+"#,
+                ),
+            ],
+        );
+
+        let found = problems
+            .iter()
+            .map(|problem| (problem.path.to_string(), problem.file.clone(), problem.pos))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            [
+                (
+                    "widgets[0].bat_name".to_owned(),
+                    Some(dir.join("base.toml")),
+                    Some((1, 24))
+                ),
+                (
+                    "widgets[1].interval".to_owned(),
+                    Some(dir.join("base.toml")),
+                    Some((2, 23))
+                ),
+                (
+                    "widgets[2].format".to_owned(),
+                    Some(dir.join("config.toml")),
+                    Some((4, 0))
+                ),
+            ]
+        );
+    }
+}
@@ -1,21 +1,36 @@
-use std::{fs, io, path::PathBuf};
+pub mod include;
+pub mod locate;
+pub mod path;
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
 
 use directories::ProjectDirs;
 use failure::{format_err, Fail};
-use lazy_static::*;
+use serde::de::DeserializeOwned;
 use serde_derive::{Deserialize, Serialize};
+use toml::{value::Table, Value};
 
+use self::path::{KeyPath, Step};
 use crate::{
-    output::ColorCfg,
-    widget::{battery, datetime, memory, mpd, net, temp, volume, WidgetKind},
+    output::{ColorCfg, SinkCfg},
+    widget::{battery, datetime, memory, mpd, net, temp, volume, WidgetCfg, WidgetKind},
 };
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum Format {
     Awesome,
     Terminal,
     I3,
+    Lemonbar,
+    Polybar,
+    Waybar,
+    Dzen2,
+    Xmobar,
+    Tmux,
 }
 
 impl std::str::FromStr for Format {
@@ -26,8 +41,15 @@ impl std::str::FromStr for Format {
             "awesome" => Ok(Format::Awesome),
             "terminal" => Ok(Format::Terminal),
             "i3" => Ok(Format::I3),
+            "lemonbar" => Ok(Format::Lemonbar),
+            "polybar" => Ok(Format::Polybar),
+            "waybar" => Ok(Format::Waybar),
+            "dzen2" => Ok(Format::Dzen2),
+            "xmobar" => Ok(Format::Xmobar),
+            "tmux" => Ok(Format::Tmux),
             _ => Err(format_err!(
-                "Invalid format specifier: {}, accepted formats: awesome, terminal, i3",
+                "Invalid format specifier: {}, accepted formats: awesome, terminal, i3, lemonbar, \
+                 polybar, waybar, dzen2, xmobar, tmux",
                 s
             )),
         }
@@ -40,6 +62,12 @@ pub enum Error {
     Io(#[cause] io::Error),
     #[fail(display = "Can't deserialize config")]
     Toml(#[cause] toml::de::Error),
+    #[fail(display = "Invalid value at line {} column {}", _0, _1)]
+    Value(usize, usize, #[cause] toml::de::Error),
+    #[fail(display = "Can't include {}", _0)]
+    Include(String, #[cause] Box<dyn Fail>),
+    #[fail(display = "{} ends up including itself", _0)]
+    Cycle(String),
 }
 
 impl From<toml::de::Error> for Error {
@@ -55,12 +83,21 @@ impl From<io::Error> for Error {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct GeneralCfg {
     pub color: bool,
     pub default_output_format: Format,
     pub update_interval: u32,
     pub enable_desktop_notifications: bool,
     pub separator: String,
+    #[serde(default)]
+    pub sink: SinkCfg,
+    /// Frames that didn't change get written again after this many milliseconds, never if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redraw_interval: Option<u32>,
+    /// Milliseconds before the same error of a widget gets notified again, defaults to a minute
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notification_cooldown: Option<u32>,
 }
 
 impl Default for GeneralCfg {
@@ -71,36 +108,169 @@ impl Default for GeneralCfg {
             enable_desktop_notifications: true,
             separator: " | ".to_owned(),
             default_output_format: Format::Terminal,
+            sink: SinkCfg::default(),
+            redraw_interval: None,
+            notification_cooldown: None,
         }
     }
 }
 
+/// An additional output, all outputs get rendered from the same widget run
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OutputCfg {
+    pub format: Format,
+    #[serde(default)]
+    pub sink: SinkCfg,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Config {
+    #[serde(default)]
     pub general: GeneralCfg,
+    #[serde(default)]
     pub colors: ColorCfg,
-    pub widgets: Vec<WidgetKind>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<OutputCfg>,
+    #[serde(default)]
+    pub widgets: Vec<WidgetCfg>,
+}
+
+/// Where a bar gets its config from, bars with different sources can run side by side
+#[derive(Debug, Clone)]
+pub struct Source {
+    pub path: PathBuf,
+    /// Tells the control sockets of the bars apart, `None` for the default config
+    pub name: Option<String>,
+}
+
+impl Source {
+    /// A path given on the command line wins over a profile in `profiles/<name>.toml`
+    pub fn new(path: Option<PathBuf>, profile: Option<String>) -> Self {
+        let config_dir = || {
+            ProjectDirs::from("com", "foldu", "statusbar-rs")
+                .unwrap()
+                .config_dir()
+                .to_owned()
+        };
+        match (path, profile) {
+            (Some(path), _) => Self {
+                name: path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned()),
+                path,
+            },
+            (None, Some(profile)) => Self {
+                path: config_dir()
+                    .join("profiles")
+                    .join(format!("{}.toml", profile)),
+                name: Some(profile),
+            },
+            (None, None) => Self {
+                path: config_dir().join("config.toml"),
+                name: None,
+            },
+        }
+    }
 }
 
-lazy_static! {
-    pub static ref CONFIG_PATH: PathBuf = {
-        ProjectDirs::from("com", "foldu", "statusbar-rs")
-            .unwrap()
-            .config_dir()
-            .join("config.toml")
-    };
+/// A config and what went into it
+pub struct Loaded {
+    pub cfg: Config,
+    pub files: Vec<PathBuf>,
+    /// Keys that don't belong to any option, most likely typos
+    pub unknown_keys: Vec<String>,
+}
+
+impl Loaded {
+    /// Goes to stderr directly because the logger hides warnings by default
+    pub fn warn_unknown_keys(&self) {
+        for key in &self.unknown_keys {
+            eprintln!("Ignoring unknown key {}", key);
+        }
+    }
 }
 
 impl Config {
+    /// A format given on the command line replaces the configured outputs
+    pub fn outputs(&self, format: Option<Format>) -> Vec<OutputCfg> {
+        match format {
+            Some(format) => vec![OutputCfg {
+                format,
+                sink: self.general.sink.clone(),
+            }],
+            None if self.outputs.is_empty() => vec![OutputCfg {
+                format: self.general.default_output_format,
+                sink: self.general.sink.clone(),
+            }],
+            None => self.outputs.clone(),
+        }
+    }
+
     #[inline]
-    pub fn load() -> Result<Self, Error> {
-        toml::from_str(&fs::read_to_string(&*CONFIG_PATH)?).map(Ok)?
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let loaded = Self::load_with_files(path)?;
+        loaded.warn_unknown_keys();
+        Ok(loaded.cfg)
+    }
+
+    /// Everything that can change the config at `path` given the `files` it was made of last
+    /// time, the config itself and the host override don't have to exist
+    pub fn watched_paths(path: &Path, files: &[PathBuf]) -> Vec<PathBuf> {
+        let mut ret = vec![path.to_owned()];
+        ret.extend(include::host_override(path));
+        ret.extend(files.iter().cloned());
+        ret
     }
 
-    fn default() -> (String, Self) {
+    /// Also returns all files the config is made of, see `include`
+    pub fn load_with_files(path: &Path) -> Result<Loaded, Error> {
+        let include::Merged { table, layers, .. } = include::load(path)?;
+        let cfg = match layers.as_slice() {
+            [layer] => parse(&layer.src)?,
+            _ => {
+                // the merged table doesn't know which file a value came from
+                for (i, layer) in layers.iter().enumerate() {
+                    parse(&layer.src).map_err(|e| match i {
+                        0 => e,
+                        _ => Error::Include(layer.path.display().to_string(), Box::new(e)),
+                    })?;
+                }
+                Value::Table(table.clone()).try_into()?
+            }
+        };
+        Ok(Loaded {
+            unknown_keys: unknown_keys(&table),
+            cfg,
+            files: layers.into_iter().map(|layer| layer.path).collect(),
+        })
+    }
+
+    /// Rewrites the config at `path` with every option spelled out, the old one is kept in
+    /// `<path>.bak`. Returns the unknown keys that got dropped.
+    pub fn migrate(path: &Path) -> Result<Vec<String>, failure::Error> {
+        let src = fs::read_to_string(path)?;
+        let table = toml::from_str::<Table>(&src)?;
+        if table.contains_key("include") {
+            return Err(format_err!(
+                "Can't migrate {}, the defaults would override the files it includes",
+                path.display()
+            ));
+        }
+        let cfg = parse(&src)?;
+        let cont = toml::to_string_pretty(&cfg)?;
+
+        let mut backup = path.as_os_str().to_owned();
+        backup.push(".bak");
+        fs::copy(path, &backup)?;
+        fs::write(path, cont)?;
+        Ok(unknown_keys(&table))
+    }
+
+    pub(crate) fn default() -> (String, Self) {
         let ret = Self {
             general: GeneralCfg::default(),
             colors: ColorCfg::default(),
+            outputs: Vec::new(),
             widgets: vec![
                 WidgetKind::Temp(temp::Cfg::default()),
                 WidgetKind::Memory(memory::Cfg::default()),
@@ -109,25 +279,30 @@ impl Config {
                 WidgetKind::Battery(battery::Cfg::default()),
                 WidgetKind::Volume(volume::Cfg::default()),
                 WidgetKind::Datetime(datetime::Cfg::default()),
-            ],
+            ]
+            .into_iter()
+            .map(WidgetCfg::from)
+            .collect(),
         };
         (toml::to_string_pretty(&ret).unwrap(), ret)
     }
 
-    pub fn write_default() -> Result<Self, Error> {
+    pub fn write_default(path: &Path) -> Result<Self, Error> {
         let (cont, ret) = Self::default();
-        fs::create_dir_all(CONFIG_PATH.parent().unwrap())?;
-        fs::write(&*CONFIG_PATH, &cont)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, &cont)?;
         Ok(ret)
     }
 
-    pub fn load_or_write_default() -> Result<Self, Error> {
-        match Self::load() {
+    pub fn load_or_write_default(path: &Path) -> Result<Self, Error> {
+        match Self::load(path) {
             Ok(cfg) => Ok(cfg),
             Err(Error::Io(io_e)) => {
                 use std::io::ErrorKind;
                 if let ErrorKind::NotFound = io_e.kind() {
-                    Self::write_default()
+                    Self::write_default(path)
                 } else {
                     Err(Error::Io(io_e))
                 }
@@ -137,6 +312,74 @@ impl Config {
     }
 }
 
+/// A config from the contents of one file. toml only knows the position of syntax errors, for
+/// other errors it's the position of the value without which the config would work.
+fn parse(src: &str) -> Result<Config, Error> {
+    toml::from_str(src).map_err(|e| {
+        if e.line_col().is_some() {
+            return Error::Toml(e);
+        }
+        let pos = toml::from_str::<Value>(src)
+            .ok()
+            .and_then(|root| locate::locate(src, &locate::culprit::<Config>(&root)));
+        match pos {
+            Some((_, (line, col))) => Error::Value(line + 1, col + 1, e),
+            None => Error::Toml(e),
+        }
+    })
+}
+
+/// Keys of `table` that deserializing it ignores, e.g. `widgets[2].bat_nmae`. Widgets are checked
+/// on their own so one broken widget doesn't hide the typos of the others.
+pub fn unknown_keys(table: &Table) -> Vec<String> {
+    let mut rest = table.clone();
+    let widgets = rest.remove("widgets");
+    let mut ret = Vec::new();
+    collect_unknown_keys::<Config>(Value::Table(rest), &KeyPath::default(), &mut ret);
+    if let Some(Value::Array(widgets)) = widgets {
+        for (i, widget) in widgets.into_iter().enumerate() {
+            let prefix = KeyPath(vec![Step::Key("widgets".to_owned()), Step::Index(i)]);
+            collect_unknown_keys::<WidgetCfg>(widget, &prefix, &mut ret);
+        }
+    }
+    ret
+}
+
+/// serde doesn't tell which keys it skipped, so every value gets replaced in turn with a string
+/// and with an array of tables. No option takes both, the key is unknown if `T` still
+/// deserializes with either of them.
+fn collect_unknown_keys<T: DeserializeOwned>(
+    mut root: Value,
+    prefix: &KeyPath,
+    ret: &mut Vec<String>,
+) {
+    if root.clone().try_into::<T>().is_err() {
+        return;
+    }
+    let probes = [
+        Value::String("\0".to_owned()),
+        Value::Array(vec![Value::Table(Table::new())]),
+    ];
+    let start = ret.len();
+    let mut todo = KeyPath::default().children(&mut root);
+    while let Some(path) = todo.pop() {
+        let old = path.get_mut(&mut root).unwrap().clone();
+        let ignored = probes.iter().all(|probe| {
+            *path.get_mut(&mut root).unwrap() = probe.clone();
+            root.clone().try_into::<T>().is_ok()
+        });
+        *path.get_mut(&mut root).unwrap() = old;
+        if ignored {
+            let mut full = prefix.clone();
+            full.0.extend(path.0);
+            ret.push(full.to_string());
+        } else {
+            todo.extend(path.children(&mut root));
+        }
+    }
+    ret[start..].sort();
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -145,4 +388,212 @@ mod tests {
     fn default_config_works() {
         Config::default();
     }
+
+    #[test]
+    fn config_round_trips() {
+        let (cont, _) = Config::default();
+        toml::from_str::<Config>(&cont).unwrap();
+
+        // the i3 output leaves actions out but a migrated config has to keep them
+        let cfg = toml::from_str::<Config>(
+            r#"
+[[widgets]]
+type = "mpd"
+
+[widgets.block]
+name = "music"
+actions = [{ button = 1, command = "mpc toggle" }]
+"#,
+        )
+        .unwrap();
+        let cfg = toml::from_str::<Config>(&toml::to_string_pretty(&cfg).unwrap()).unwrap();
+        let block = cfg.widgets[0].block.as_ref().unwrap();
+        assert_eq!(block.name, "music");
+        assert_eq!(block.actions[0].button, 1);
+        assert_eq!(block.actions[0].command, "mpc toggle");
+    }
+
+    #[test]
+    fn missing_options_get_defaults() {
+        let table = toml::from_str::<Table>(
+            r##"
+[general]
+colr = false
+update_interval = 500
+
+[colors.hex]
+good = "#00AA00"
+
+[[widgets]]
+type = "battery"
+bat_nmae = "BAT1"
+fromat = ""
+
+[[widgets]]
+type = "net"
+
+[widgets.block]
+instance = ""
+"##,
+        )
+        .unwrap();
+        let cfg: Config = Value::Table(table.clone()).try_into().unwrap();
+        assert_eq!(cfg.general.update_interval, 500);
+        assert!(cfg.general.color);
+        assert_eq!(cfg.colors.hex.good.as_ref(), "#00AA00");
+        assert_eq!(
+            unknown_keys(&table),
+            ["general.colr", "widgets[0].bat_nmae", "widgets[0].fromat"]
+        );
+
+        let (cont, _) = Config::default();
+        assert!(unknown_keys(&toml::from_str(&cont).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn errors_have_positions() {
+        let dir = std::env::temp_dir().join(format!("statusbar-errors-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let config = dir.join("config.toml");
+        fs::write(&config, "[general]\nupdate_interval = \"x\"\n").unwrap();
+        match Config::load_with_files(&config) {
+            Err(Error::Value(2, 1, _)) => {}
+            res => panic!("{:?}", res.map(|loaded| loaded.cfg)),
+        }
+
+        let base = dir.join("base.toml");
+        fs::write(&config, "include = [\"base.toml\"]\n").unwrap();
+        fs::write(&base, "\n[[widgets]]\ntype = \"memory\"\ninterval = -1\n").unwrap();
+        let e = Config::load_with_files(&config).err().unwrap();
+        assert_eq!(e.to_string(), format!("Can't include {}", base.display()));
+        let cause = e.cause().unwrap().to_string();
+        assert!(
+            cause.starts_with("Invalid value at line 4 column 1"),
+            "{}",
+            cause
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn widgets_only_need_a_type() {
+        let cfg = toml::from_str::<Config>(
+            r#"
+[[widgets]]
+type = "battery"
+
+[[widgets]]
+type = "datetime"
+
+[[widgets]]
+type = "mpd"
+
+[[widgets]]
+type = "volume"
+
+[widgets.backend]
+type = "alsa"
+
+[[widgets]]
+type = "net"
+interface = { type = "dynamic" }
+
+[[widgets]]
+type = "memory"
+
+[[widgets]]
+type = "temp"
+
+[[widgets]]
+type = "cpu_usage"
+"#,
+        )
+        .unwrap();
+        let names = cfg
+            .widgets
+            .iter()
+            .map(|widget| widget.kind.name())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "battery",
+                "datetime",
+                "mpd",
+                "volume",
+                "net",
+                "memory",
+                "temp",
+                "cpu_usage"
+            ]
+        );
+
+        let cfg = toml::from_str::<Config>(
+            "[general]
+update_interval = 500",
+        )
+        .unwrap();
+        assert!(cfg.widgets.is_empty());
+    }
+
+    #[test]
+    fn widget_block_cfg() {
+        let cfg: Config = toml::from_str(&format!(
+            "{}{}",
+            Config::default().0,
+            r##"
+[[widgets]]
+type = "datetime"
+timezone = "local"
+format = "%H:%M"
+
+[widgets.block]
+name = "clock"
+background = "#112233"
+min_width = "00:00"
+align = "center"
+"##
+        ))
+        .unwrap();
+
+        let clock = cfg.widgets.last().unwrap();
+        assert_eq!(clock.kind.name(), "datetime");
+        let block = clock.block.as_ref().unwrap();
+        assert_eq!(block.name, "clock");
+        assert!(block.instance.is_empty());
+        assert_eq!(block.background.as_ref().unwrap().as_ref(), "#112233");
+    }
+
+    #[test]
+    fn multiple_outputs() {
+        let mut cfg = Config::default().1;
+        assert_eq!(cfg.outputs(None).len(), 1);
+
+        cfg.outputs = toml::from_str::<Config>(&format!(
+            "{}{}",
+            Config::default().0,
+            r#"
+[[outputs]]
+format = "i3"
+
+[[outputs]]
+format = "tmux"
+sink = { type = "file", path = "/tmp/statusbar" }
+"#
+        ))
+        .unwrap()
+        .outputs;
+        let outputs = cfg.outputs(None);
+        assert_eq!(outputs[0].format, Format::I3);
+        assert_eq!(outputs[0].sink, SinkCfg::Stdout);
+        assert_eq!(outputs[1].format, Format::Tmux);
+        assert_eq!(
+            outputs[1].sink,
+            SinkCfg::File {
+                path: "/tmp/statusbar".into()
+            }
+        );
+
+        assert_eq!(cfg.outputs(Some(Format::Terminal)).len(), 1);
+    }
 }
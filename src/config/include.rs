@@ -0,0 +1,236 @@
+//! Configs made of several files. `include = [...]` pulls in files that the including file goes
+//! on top of, `hosts/<hostname>.toml` next to the config goes on top of everything.
+
+use std::{
+    fs, mem,
+    path::{Path, PathBuf},
+};
+
+use log::*;
+use toml::value::{Table, Value};
+
+use super::Error;
+
+/// One file of a config
+pub struct Layer {
+    pub path: PathBuf,
+    pub src: String,
+}
+
+/// Layer and index in its `widgets` of every file that set a part of a widget
+pub type Origin = Vec<(usize, usize)>;
+
+/// The config at `path` with its includes and the override of this host
+pub struct Merged {
+    pub table: Table,
+    /// Every file that went into `table` starting with `path`
+    pub layers: Vec<Layer>,
+    /// Where each widget of `table` came from
+    pub origins: Vec<Origin>,
+}
+
+pub fn load(path: &Path) -> Result<Merged, Error> {
+    let mut layers = Vec::new();
+    let (mut table, mut origins) = read(path, &mut Vec::new(), &mut layers)?;
+    if let Some(host) = host_override(path).filter(|host| host.exists()) {
+        let (mut top, top_origins) = read(&host, &mut Vec::new(), &mut layers)
+            .map_err(|e| Error::Include(host.display().to_string(), Box::new(e)))?;
+        let widgets = match top.get_mut("widgets") {
+            Some(Value::Array(widgets)) => mem::take(widgets),
+            _ => Vec::new(),
+        };
+        merge(&mut table, top);
+        if let Some(Value::Array(base)) = table.get_mut("widgets") {
+            merge_widgets(base, &mut origins, widgets.into_iter().zip(top_origins));
+        }
+    }
+    Ok(Merged {
+        table,
+        layers,
+        origins,
+    })
+}
+
+// `stack` holds the files that are being read to catch include cycles
+fn read(
+    path: &Path,
+    stack: &mut Vec<PathBuf>,
+    layers: &mut Vec<Layer>,
+) -> Result<(Table, Vec<Origin>), Error> {
+    let path = fs::canonicalize(path)?;
+    let src = fs::read_to_string(&path)?;
+    let mut table = toml::from_str::<Table>(&src)?;
+    let own = match table.get("widgets") {
+        Some(Value::Array(widgets)) => (0..widgets.len())
+            .map(|i| vec![(layers.len(), i)])
+            .collect(),
+        _ => Vec::new(),
+    };
+    layers.push(Layer {
+        path: path.clone(),
+        src,
+    });
+    let includes = match table.remove("include") {
+        Some(includes) => includes.try_into::<Vec<PathBuf>>()?,
+        None => return Ok((table, own)),
+    };
+
+    let dir = path.parent().unwrap_or_else(|| Path::new("/")).to_owned();
+    stack.push(path);
+    let mut base = Table::new();
+    let mut origins = Vec::new();
+    for include in includes {
+        let include = dir.join(include);
+        let (layer, layer_origins) = match fs::canonicalize(&include) {
+            Ok(ref canonical) if stack.contains(canonical) => {
+                Err(Error::Cycle(include.display().to_string()))
+            }
+            _ => read(&include, stack, layers),
+        }
+        .map_err(|e| Error::Include(include.display().to_string(), Box::new(e)))?;
+        merge(&mut base, layer);
+        origins.extend(layer_origins);
+    }
+    stack.pop();
+    merge(&mut base, table);
+    origins.extend(own);
+    Ok((base, origins))
+}
+
+pub fn host_override(path: &Path) -> Option<PathBuf> {
+    let mut buf = [0u8; 256];
+    let hostname = match nix::unistd::gethostname(&mut buf) {
+        Ok(hostname) => hostname.to_string_lossy().into_owned(),
+        Err(e) => {
+            warn!("Can't get hostname, ignoring host overrides: {}", e);
+            return None;
+        }
+    };
+    Some(
+        path.parent()?
+            .join("hosts")
+            .join(format!("{}.toml", hostname)),
+    )
+}
+
+/// Tables get merged key by key, widgets of the top table follow the widgets of the base,
+/// everything else gets replaced
+fn merge(base: &mut Table, top: Table) {
+    for (key, value) in top {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(top)) => merge(base, top),
+            (Some(Value::Array(base)), Value::Array(top)) if key == "widgets" => base.extend(top),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Widgets of a host override get merged into the first widget of the same type and
+/// `block.instance` instead so a host can e.g. change the battery name
+fn merge_widgets(
+    base: &mut Vec<Value>,
+    origins: &mut Vec<Origin>,
+    top: impl Iterator<Item = (Value, Origin)>,
+) {
+    for (widget, origin) in top {
+        match base.iter().position(|other| same_widget(other, &widget)) {
+            Some(i) => {
+                match (&mut base[i], widget) {
+                    (Value::Table(base), Value::Table(top)) => merge(base, top),
+                    (base, widget) => *base = widget,
+                }
+                if let Some(base) = origins.get_mut(i) {
+                    base.extend(origin);
+                }
+            }
+            None => {
+                base.push(widget);
+                origins.push(origin);
+            }
+        }
+    }
+}
+
+// an override without an instance matches every instance
+fn same_widget(base: &Value, top: &Value) -> bool {
+    let instance = |widget: &Value| {
+        widget
+            .get("block")
+            .and_then(|block| block.get("instance"))
+            .cloned()
+    };
+    base.get("type") == top.get("type") && instance(top).is_none_or(|i| instance(base) == Some(i))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn includes_and_host_overrides() {
+        let dir = std::env::temp_dir().join(format!("statusbar-include-{}", std::process::id()));
+        let config = dir.join("config.toml");
+        let host = host_override(&config).unwrap();
+        fs::create_dir_all(host.parent().unwrap()).unwrap();
+        let write = |path: &Path, cont: &str| fs::write(path, cont).unwrap();
+        write(
+            &dir.join("base.toml"),
+            r#"
+general = { separator = " | ", update_interval = 1000 }
+
+[[widgets]]
+type = "battery"
+bat_name = "BAT0"
+format = "{charge}"
+
+[[widgets]]
+type = "net"
+if_name = "eth0"
+"#,
+        );
+        write(
+            &config,
+            r#"
+include = ["base.toml"]
+general = { separator = " " }
+
+[[widgets]]
+type = "datetime"
+"#,
+        );
+        write(
+            &host,
+            r#"
+[[widgets]]
+type = "battery"
+bat_name = "BAT1"
+"#,
+        );
+
+        let Merged {
+            table,
+            layers,
+            origins,
+        } = load(&config).unwrap();
+        assert_eq!(layers.len(), 3);
+        let general = &table["general"];
+        assert_eq!(general["separator"].as_str(), Some(" "));
+        assert_eq!(general["update_interval"].as_integer(), Some(1000));
+        let widgets = table["widgets"].as_array().unwrap();
+        let types = widgets
+            .iter()
+            .map(|widget| widget["type"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(types, ["battery", "net", "datetime"]);
+        assert_eq!(widgets[0]["bat_name"].as_str(), Some("BAT1"));
+        assert_eq!(widgets[0]["format"].as_str(), Some("{charge}"));
+        // layers go config, base, host
+        assert_eq!(origins, [vec![(1, 0), (2, 0)], vec![(1, 1)], vec![(0, 0)]]);
+
+        write(&dir.join("base.toml"), "include = [\"config.toml\"]");
+        assert!(load(&config).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,324 @@
+//! Positions of values in config files, toml only knows them for syntax errors
+
+use serde::de::DeserializeOwned;
+use toml::Value;
+
+use super::path::{KeyPath, Step};
+
+/// The value that keeps `root` from deserializing into `T`: the innermost one without which it
+/// works. Empty if taking out values doesn't help, e.g. when an option is missing.
+pub fn culprit<T: DeserializeOwned>(root: &Value) -> KeyPath {
+    let mut root = root.clone();
+    let mut found = KeyPath::default();
+    'descend: loop {
+        for child in found.children(&mut root) {
+            let mut without = root.clone();
+            child.remove(&mut without);
+            if without.try_into::<T>().is_ok() {
+                found = child;
+                continue 'descend;
+            }
+        }
+        return found;
+    }
+}
+
+/// Zero based line and column of the value at `path` in `src` or of the closest table or array
+/// that contains it, with the number of steps of `path` that got found
+pub fn locate(src: &str, path: &KeyPath) -> Option<(usize, (usize, usize))> {
+    let mut scanner = Scanner {
+        src,
+        pos: 0,
+        found: Vec::new(),
+    };
+    scanner.document();
+    let (found, offset) = scanner
+        .found
+        .into_iter()
+        .filter(|(found, _)| path.0.starts_with(&found.0))
+        .rev()
+        .max_by_key(|(found, _)| found.0.len())?;
+    let before = &src[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Some((
+        found.0.len(),
+        (before.matches('\n').count(), offset - line_start),
+    ))
+}
+
+/// Just enough of a TOML parser to know where keys and array elements start, gives up at the
+/// first syntax error
+struct Scanner<'a> {
+    src: &'a str,
+    pos: usize,
+    // byte offsets
+    found: Vec<(KeyPath, usize)>,
+}
+
+impl<'a> Scanner<'a> {
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) {
+        if let Some(c) = self.peek() {
+            self.pos += c.len_utf8();
+        }
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        let found = self.rest().starts_with(s);
+        if found {
+            self.pos += s.len();
+        }
+        found
+    }
+
+    fn skip_line(&mut self) {
+        self.pos = match self.rest().find('\n') {
+            Some(i) => self.pos + i + 1,
+            None => self.src.len(),
+        };
+    }
+
+    // newlines and comments only if `lines` is set
+    fn skip_space(&mut self, lines: bool) {
+        loop {
+            match self.peek() {
+                Some(' ') | Some('\t') => self.bump(),
+                Some('\n') | Some('\r') if lines => self.bump(),
+                Some('#') if lines => self.skip_line(),
+                _ => return,
+            }
+        }
+    }
+
+    fn document(&mut self) -> Option<()> {
+        let mut table = KeyPath::default();
+        // tables so far in every array of tables
+        let mut arrays = Vec::<(KeyPath, usize)>::new();
+        loop {
+            self.skip_space(true);
+            let start = self.pos;
+            if self.peek()? == '[' {
+                let array = self.eat("[[");
+                if !array {
+                    self.bump();
+                }
+                let keys = self.key()?;
+                table = KeyPath::default();
+                for (i, key) in keys.iter().enumerate() {
+                    table.0.push(Step::Key((*key).to_owned()));
+                    let count = arrays.iter_mut().find(|(path, _)| *path == table);
+                    match count {
+                        Some((_, count)) if array && i == keys.len() - 1 => {
+                            table.0.push(Step::Index(*count));
+                            *count += 1;
+                        }
+                        Some((_, count)) => table.0.push(Step::Index(*count - 1)),
+                        None if array && i == keys.len() - 1 => {
+                            arrays.push((table.clone(), 1));
+                            table.0.push(Step::Index(0));
+                        }
+                        None => {}
+                    }
+                }
+                self.found.push((table.clone(), start));
+            } else {
+                let path = self.key_value(&table, start)?;
+                self.value(&path)?;
+            }
+            self.skip_line();
+        }
+    }
+
+    /// Reads `a.b = ` and returns the path of the value that follows
+    fn key_value(&mut self, table: &KeyPath, start: usize) -> Option<KeyPath> {
+        let mut path = table.clone();
+        for key in self.key()? {
+            path.0.push(Step::Key(key.to_owned()));
+            self.found.push((path.clone(), start));
+        }
+        if !self.eat("=") {
+            return None;
+        }
+        self.skip_space(false);
+        Some(path)
+    }
+
+    fn key(&mut self) -> Option<Vec<&'a str>> {
+        let mut keys = Vec::new();
+        loop {
+            self.skip_space(false);
+            let key = match self.peek()? {
+                '"' | '\'' => self.string()?,
+                _ => {
+                    let rest = self.rest();
+                    let len = rest
+                        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+                        .unwrap_or(rest.len());
+                    if len == 0 {
+                        return None;
+                    }
+                    self.pos += len;
+                    &rest[..len]
+                }
+            };
+            keys.push(key);
+            self.skip_space(false);
+            if !self.eat(".") {
+                return Some(keys);
+            }
+        }
+    }
+
+    /// Skips a string and returns what's between the quotes, escapes aren't resolved
+    fn string(&mut self) -> Option<&'a str> {
+        let quote = ["\"\"\"", "'''", "\"", "'"]
+            .iter()
+            .find(|quote| self.rest().starts_with(*quote))?;
+        self.pos += quote.len();
+        let start = self.pos;
+        loop {
+            if self.rest().starts_with(quote) {
+                let ret = &self.src[start..self.pos];
+                self.pos += quote.len();
+                return Some(ret);
+            }
+            if self.peek()? == '\\' && quote.starts_with('"') {
+                self.bump();
+            }
+            self.bump();
+        }
+    }
+
+    fn value(&mut self, path: &KeyPath) -> Option<()> {
+        match self.peek()? {
+            '"' | '\'' => {
+                self.string()?;
+            }
+            '[' => {
+                self.bump();
+                for i in 0.. {
+                    self.skip_space(true);
+                    if self.eat("]") {
+                        break;
+                    }
+                    let mut element = path.clone();
+                    element.0.push(Step::Index(i));
+                    self.found.push((element.clone(), self.pos));
+                    self.value(&element)?;
+                    self.skip_space(true);
+                    if !self.eat(",") {
+                        self.skip_space(true);
+                        return if self.eat("]") { Some(()) } else { None };
+                    }
+                }
+            }
+            '{' => {
+                self.bump();
+                loop {
+                    self.skip_space(false);
+                    if self.eat("}") {
+                        break;
+                    }
+                    let start = self.pos;
+                    let child = self.key_value(path, start)?;
+                    self.value(&child)?;
+                    self.skip_space(false);
+                    if !self.eat(",") {
+                        self.skip_space(false);
+                        return if self.eat("}") { Some(()) } else { None };
+                    }
+                }
+            }
+            _ => {
+                // numbers, booleans and dates end at the next delimiter
+                let rest = self.rest();
+                let len = rest
+                    .find(|c| [',', ']', '}', '\n', '#'].contains(&c))
+                    .unwrap_or(rest.len());
+                if len == 0 {
+                    return None;
+                }
+                self.pos += len;
+            }
+        }
+        Some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(s: &str) -> KeyPath {
+        let mut ret = KeyPath::default();
+        for part in s.split('.') {
+            match part.find('[') {
+                Some(i) => {
+                    ret.0.push(Step::Key(part[..i].to_owned()));
+                    let index = part[i + 1..part.len() - 1].parse().unwrap();
+                    ret.0.push(Step::Index(index));
+                }
+                None => ret.0.push(Step::Key(part.to_owned())),
+            }
+        }
+        ret
+    }
+
+    #[test]
+    fn values_get_located() {
+        let src = r#"
+[general]
+separator = "] = ["  # not a table
+
+[[widgets]]
+type = "datetime"
+
+[[widgets]]
+type = "net"
+block = { name = "net", actions = [
+    { button = 1, command = "nm-connection-editor" },
+    { button = 3, command = 'x' },
+] }
+
+[[widgets.alerts]]
+key = "up"
+when.if_name = "wlan0"
+"#;
+        let at = |s: &str| locate(src, &path(s));
+        assert_eq!(at("general.separator"), Some((2, (2, 0))));
+        assert_eq!(at("widgets[1]"), Some((2, (7, 0))));
+        assert_eq!(at("widgets[1].block.name"), Some((4, (9, 10))));
+        assert_eq!(
+            at("widgets[1].block.actions[1].command"),
+            Some((6, (11, 18)))
+        );
+        assert_eq!(at("widgets[1].alerts[0].when.if_name"), Some((6, (16, 0))));
+        // the closest thing that exists
+        assert_eq!(at("widgets[0].format"), Some((2, (4, 0))));
+        assert_eq!(at("outputs[0]"), None);
+    }
+
+    #[test]
+    fn culprit_is_innermost() {
+        let root = toml::from_str::<Value>(
+            r#"
+[[widgets]]
+type = "datetime"
+
+[[widgets]]
+type = "memory"
+interval = "often"
+"#,
+        )
+        .unwrap();
+        let found = culprit::<crate::config::Config>(&root);
+        assert_eq!(found, path("widgets[1].interval"));
+    }
+}
@@ -0,0 +1,74 @@
+//! Where a value sits in a config, e.g. `widgets[2].block.name`
+
+use std::fmt;
+
+use toml::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyPath(pub Vec<Step>);
+
+impl KeyPath {
+    /// The value at this path in `root`
+    pub fn get_mut<'a>(&self, root: &'a mut Value) -> Option<&'a mut Value> {
+        self.0
+            .iter()
+            .try_fold(root, |value, step| match (value, step) {
+                (Value::Table(table), Step::Key(key)) => table.get_mut(key),
+                (Value::Array(array), Step::Index(i)) => array.get_mut(*i),
+                _ => None,
+            })
+    }
+
+    /// Takes the value at this path out of `root`
+    pub fn remove(&self, root: &mut Value) {
+        let (last, parent) = match self.0.split_last() {
+            Some(split) => split,
+            None => return,
+        };
+        match (KeyPath(parent.to_vec()).get_mut(root), last) {
+            (Some(Value::Table(table)), Step::Key(key)) => {
+                table.remove(key);
+            }
+            (Some(Value::Array(array)), &Step::Index(i)) if i < array.len() => {
+                array.remove(i);
+            }
+            _ => {}
+        }
+    }
+
+    /// Paths of the values directly inside the value at this path
+    pub fn children(&self, root: &mut Value) -> Vec<KeyPath> {
+        let steps = match self.get_mut(root) {
+            Some(Value::Table(table)) => table.keys().cloned().map(Step::Key).collect(),
+            Some(Value::Array(array)) => (0..array.len()).map(Step::Index).collect(),
+            _ => Vec::new(),
+        };
+        steps
+            .into_iter()
+            .map(|step| {
+                let mut child = self.clone();
+                child.0.push(step);
+                child
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for KeyPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, step) in self.0.iter().enumerate() {
+            match step {
+                Step::Key(key) if i == 0 => write!(f, "{}", key)?,
+                Step::Key(key) => write!(f, ".{}", key)?,
+                Step::Index(index) => write!(f, "[{}]", index)?,
+            }
+        }
+        Ok(())
+    }
+}
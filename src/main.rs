@@ -1,3 +1,4 @@
+mod check;
 mod config;
 mod output;
 mod parse;
@@ -5,8 +6,11 @@ mod statusbar;
 mod util;
 mod widget;
 
-use crate::config::{Config, Format};
+use std::path::PathBuf;
 
+use crate::config::{Config, Format, Source};
+
+use failure::format_err;
 use structopt::StructOpt;
 
 #[derive(StructOpt, Debug)]
@@ -17,19 +21,73 @@ struct Opt {
 
     #[structopt(short = "f", long = "format")]
     format: Option<Format>,
+
+    /// Use this config instead of the default one
+    #[structopt(short = "c", long = "config", parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    /// Use profiles/<name>.toml in the config directory, e.g. for a bar on each monitor
+    #[structopt(short = "p", long = "profile", conflicts_with = "config")]
+    profile: Option<String>,
+
+    /// Check the config without running any widget
+    #[structopt(long = "check")]
+    check: bool,
+
+    /// Rewrite the config with every option spelled out, keeps the old one as <config>.bak
+    #[structopt(long = "migrate")]
+    migrate: bool,
+
+    /// Print a single frame and exit, fails if a widget failed
+    #[structopt(long = "once")]
+    once: bool,
+
+    #[structopt(subcommand)]
+    cmd: Option<Cmd>,
+}
+
+#[derive(StructOpt, Debug)]
+enum Cmd {
+    /// Send a command to the running bar: refresh, reload, hide <widget>, show <widget>, dump or
+    /// set-format <format>
+    #[structopt(name = "ctl")]
+    Ctl {
+        #[structopt(raw(required = "true"))]
+        command: Vec<String>,
+    },
 }
 
 fn run() -> Result<(), failure::Error> {
     let opt = Opt::from_args();
 
+    let source = Source::new(opt.config, opt.profile);
+
+    if let Some(Cmd::Ctl { command }) = opt.cmd {
+        return statusbar::ctl(&command, source.name.as_deref());
+    }
+
+    if opt.check {
+        return check::run(&source.path);
+    }
+
+    if opt.migrate {
+        for key in Config::migrate(&source.path)? {
+            eprintln!("Dropped unknown key {}", key);
+        }
+        println!("Migrated {}", source.path.display());
+        return Ok(());
+    }
+
     let cfg = if opt.write_default {
-        Config::write_default()?
+        Config::write_default(&source.path)?
     } else {
-        Config::load_or_write_default()?
+        Config::load_or_write_default(&source.path)?
     };
 
-    statusbar::run(cfg, opt.format);
-    Ok(())
+    match statusbar::run(cfg, source, opt.format, opt.once) {
+        0 => Ok(()),
+        _ => Err(format_err!("Some widgets failed")),
+    }
 }
 
 fn main() {
@@ -1,38 +1,343 @@
 pub mod awesome;
 mod color;
+pub mod dzen2;
+mod escape;
 pub mod i3;
+pub mod lemonbar;
+mod recording;
+mod sink;
+mod tagged;
 pub mod terminal;
+pub mod tmux;
+pub mod waybar;
+pub mod xmobar;
 
-pub use self::color::ColorCfg;
+pub use self::{
+    color::ColorCfg,
+    i3::ClickEvent,
+    recording::Recording,
+    sink::{Sink, SinkCfg},
+};
 
-use std::fmt;
+use std::{
+    fmt::{self, Write},
+    time::Duration,
+};
 
-use crate::config::Format;
+use formatter::{DelayedFormat, FormatMap};
+use serde_derive::{Deserialize, Serialize};
 
+use self::color::HexRgb;
+use crate::config::{Format, OutputCfg};
+
+/// `write` and `write_colored` take plain text that gets escaped by backends that understand
+/// markup, `write_markup` and `write_markup_colored` take trusted markup that gets passed through
+/// as is
 pub trait Output {
     fn init(&mut self) {}
     fn start(&mut self) {}
+    fn start_block(&mut self, _: &Block) {}
+    /// What the widget of the current block computed, for bars that show more than text
+    fn block_values(&mut self, _: &FormatMap) {}
     fn write(&mut self, _: fmt::Arguments);
     fn write_sep(&mut self) {}
     fn write_colored(&mut self, _: Color, _: fmt::Arguments);
+    fn write_markup(&mut self, s: fmt::Arguments) {
+        self.write(s)
+    }
+    fn write_markup_colored(&mut self, c: Color, s: fmt::Arguments) {
+        self.write_colored(c, s)
+    }
+    /// A filled in format string of a widget
+    fn write_format(&mut self, f: DelayedFormat<'_>) {
+        self.write(format_args!("{}", f))
+    }
+    fn write_format_colored(&mut self, c: Color, f: DelayedFormat<'_>) {
+        self.write_colored(c, format_args!("{}", f))
+    }
     fn finish(&mut self) {}
+    /// Called once before exiting
+    fn close(&mut self) {}
 
     fn set_sep(&mut self, _: String) {}
     fn set_colors(&mut self, _: &ColorCfg);
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Passes everything written as trusted markup to the wrapped output, for widgets whose format
+/// strings are markup. The values in the format strings still get escaped, they can be anything
+/// like a song title.
+pub struct TrustedMarkup<'a>(pub &'a mut dyn Output);
+
+fn escape_pango(s: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    escape::Escape(escape::PANGO, f).write_str(s)
+}
+
+impl Output for TrustedMarkup<'_> {
+    fn init(&mut self) {
+        self.0.init()
+    }
+
+    fn start(&mut self) {
+        self.0.start()
+    }
+
+    fn start_block(&mut self, block: &Block) {
+        self.0.start_block(block)
+    }
+
+    fn block_values(&mut self, values: &FormatMap) {
+        self.0.block_values(values)
+    }
+
+    fn write(&mut self, s: fmt::Arguments) {
+        self.0.write_markup(s)
+    }
+
+    fn write_sep(&mut self) {
+        self.0.write_sep()
+    }
+
+    fn write_colored(&mut self, c: Color, s: fmt::Arguments) {
+        self.0.write_markup_colored(c, s)
+    }
+
+    fn write_markup(&mut self, s: fmt::Arguments) {
+        self.0.write_markup(s)
+    }
+
+    fn write_markup_colored(&mut self, c: Color, s: fmt::Arguments) {
+        self.0.write_markup_colored(c, s)
+    }
+
+    fn write_format(&mut self, f: DelayedFormat<'_>) {
+        self.0
+            .write_markup(format_args!("{}", f.escape_values(escape_pango)))
+    }
+
+    fn write_format_colored(&mut self, c: Color, f: DelayedFormat<'_>) {
+        self.0
+            .write_markup_colored(c, format_args!("{}", f.escape_values(escape_pango)))
+    }
+
+    fn finish(&mut self) {
+        self.0.finish()
+    }
+
+    fn close(&mut self) {
+        self.0.close()
+    }
+
+    fn set_sep(&mut self, sep: String) {
+        self.0.set_sep(sep)
+    }
+
+    fn set_colors(&mut self, colors: &ColorCfg) {
+        self.0.set_colors(colors)
+    }
+}
+
+/// Renders everything written into several outputs so widgets only run once per tick
+pub struct Tee(pub Vec<Box<dyn Output>>);
+
+impl Output for Tee {
+    fn init(&mut self) {
+        for out in &mut self.0 {
+            out.init();
+        }
+    }
+
+    fn start(&mut self) {
+        for out in &mut self.0 {
+            out.start();
+        }
+    }
+
+    fn start_block(&mut self, block: &Block) {
+        for out in &mut self.0 {
+            out.start_block(block);
+        }
+    }
+
+    fn block_values(&mut self, values: &FormatMap) {
+        for out in &mut self.0 {
+            out.block_values(values);
+        }
+    }
+
+    fn write(&mut self, s: fmt::Arguments) {
+        for out in &mut self.0 {
+            out.write(s);
+        }
+    }
+
+    fn write_sep(&mut self) {
+        for out in &mut self.0 {
+            out.write_sep();
+        }
+    }
+
+    fn write_colored(&mut self, c: Color, s: fmt::Arguments) {
+        for out in &mut self.0 {
+            out.write_colored(c, s);
+        }
+    }
+
+    fn write_markup(&mut self, s: fmt::Arguments) {
+        for out in &mut self.0 {
+            out.write_markup(s);
+        }
+    }
+
+    fn write_markup_colored(&mut self, c: Color, s: fmt::Arguments) {
+        for out in &mut self.0 {
+            out.write_markup_colored(c, s);
+        }
+    }
+
+    fn finish(&mut self) {
+        for out in &mut self.0 {
+            out.finish();
+        }
+    }
+
+    fn close(&mut self) {
+        for out in &mut self.0 {
+            out.close();
+        }
+    }
+
+    fn set_sep(&mut self, sep: String) {
+        for out in &mut self.0 {
+            out.set_sep(sep.clone());
+        }
+    }
+
+    fn set_colors(&mut self, colors: &ColorCfg) {
+        for out in &mut self.0 {
+            out.set_colors(colors);
+        }
+    }
+}
+
+/// Ordered from least to most urgent, `Stale` is used for values that couldn't be refreshed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Color {
+    Stale,
     Good,
     Mediocre,
     Bad,
 }
 
-pub fn output_from_format(sep: String, colors: ColorCfg, fmt: Format) -> Box<dyn Output> {
+/// Metadata for everything written after `Output::start_block`, `name` and `instance` identify
+/// the widget and default to its type and index, everything else is only used by bars that
+/// understand it
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Block {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub instance: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub short_text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background: Option<HexRgb>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border: Option<HexRgb>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border_top: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border_right: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border_bottom: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border_left: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub separator: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub separator_block_width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub urgent: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_width: Option<MinWidth>,
+    /// i3bar aligns the text inside of `min_width`, lemonbar puts the block into that part of
+    /// the bar
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub align: Option<Align>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub markup: Option<Markup>,
+    /// Commands the bar runs when the block gets clicked
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<Action>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Action {
+    pub button: u8,
+    pub command: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum MinWidth {
+    Pixels(u32),
+    Text(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Markup {
+    None,
+    Pango,
+}
+
+pub fn output_from_format(
+    sep: String,
+    colors: ColorCfg,
+    fmt: Format,
+    sink: Sink,
+) -> Box<dyn Output> {
     // FIXME: references to sep
     match fmt {
-        Format::Awesome => Box::new(awesome::Output::new(&sep, colors)),
-        Format::Terminal => Box::new(terminal::Output::new(&sep, colors)),
-        Format::I3 => Box::new(i3::Output::new(colors)),
+        Format::Awesome => Box::new(awesome::Output::new(&sep, colors, sink)),
+        Format::Terminal => Box::new(terminal::Output::new(&sep, colors, sink)),
+        Format::I3 => Box::new(i3::Output::new(colors, sink)),
+        Format::Lemonbar => Box::new(lemonbar::Output::new(&sep, colors, sink)),
+        Format::Polybar => Box::new(lemonbar::Output::polybar(&sep, colors, sink)),
+        Format::Waybar => Box::new(waybar::Output::new(&sep, sink)),
+        Format::Dzen2 => Box::new(dzen2::Output::new(&sep, colors, sink)),
+        Format::Xmobar => Box::new(xmobar::Output::new(&sep, colors, sink)),
+        Format::Tmux => Box::new(tmux::Output::new(&sep, colors, sink)),
+    }
+}
+
+/// `redraw_interval` in milliseconds
+pub fn output_from_cfgs(
+    sep: String,
+    colors: ColorCfg,
+    cfgs: &[OutputCfg],
+    redraw_interval: Option<u32>,
+) -> Box<dyn Output> {
+    let redraw = redraw_interval.map(|ms| Duration::from_millis(u64::from(ms)));
+    let mut outputs = cfgs.iter().map(|cfg| {
+        output_from_format(
+            sep.clone(),
+            colors.clone(),
+            cfg.format,
+            Sink::new(&cfg.sink, redraw),
+        )
+    });
+    if cfgs.len() == 1 {
+        outputs.next().unwrap()
+    } else {
+        Box::new(Tee(outputs.collect()))
     }
 }
@@ -2,13 +2,17 @@ use std::fmt::{self, Write};
 
 use serde_derive::{Deserialize, Serialize};
 
-use super::color::{ColorCfg, GColors, HexRgb};
-use crate::output::Color;
+use super::{
+    color::{ColorCfg, GColors, HexRgb},
+    escape::{Escape, PANGO},
+};
+use crate::output::{Color, Sink};
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct Output {
     buf: String,
     cfg: Cfg,
+    sink: Sink,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,13 +22,14 @@ pub struct Cfg {
 }
 
 impl Output {
-    pub fn new(sep: &str, colors: ColorCfg) -> Self {
+    pub fn new(sep: &str, colors: ColorCfg, sink: Sink) -> Self {
         Self {
             buf: String::new(),
             cfg: Cfg {
                 separator: sep.to_owned(),
                 colors: colors.hex,
             },
+            sink,
         }
     }
 }
@@ -35,16 +40,27 @@ impl super::Output for Output {
     }
 
     fn write(&mut self, s: fmt::Arguments) {
-        write!(self.buf, "{}", s).unwrap()
+        Escape(PANGO, &mut self.buf).write_fmt(s).unwrap()
     }
 
     fn write_colored(&mut self, c: Color, s: fmt::Arguments) {
-        let color = match c {
-            Color::Good => self.cfg.colors.good.as_ref(),
-            Color::Mediocre => self.cfg.colors.mediocre.as_ref(),
-            Color::Bad => self.cfg.colors.bad.as_ref(),
-        };
-        write!(self.buf, "<span color=\"{}\">{}</span>", color, s).unwrap()
+        write!(self.buf, "<span color=\"{}\">", self.cfg.colors.get(c)).unwrap();
+        self.write(s);
+        self.buf.push_str("</span>");
+    }
+
+    fn write_markup(&mut self, s: fmt::Arguments) {
+        self.buf.write_fmt(s).unwrap()
+    }
+
+    fn write_markup_colored(&mut self, c: Color, s: fmt::Arguments) {
+        write!(
+            self.buf,
+            "<span color=\"{}\">{}</span>",
+            self.cfg.colors.get(c),
+            s
+        )
+        .unwrap()
     }
 
     fn write_sep(&mut self) {
@@ -58,7 +74,7 @@ impl super::Output for Output {
     }
 
     fn finish(&mut self) {
-        println!("{}", self.buf);
+        self.sink.write_frame(format_args!("{}", self.buf));
     }
 
     fn set_colors(&mut self, colors: &ColorCfg) {
@@ -69,3 +85,25 @@ impl super::Output for Output {
         self.cfg.separator = sep;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{Output as _, Sink, TrustedMarkup};
+
+    #[test]
+    fn widget_text_gets_escaped() {
+        let mut out = Output::new(" | ", ColorCfg::default(), Sink::default());
+        out.write(format_args!("{}", "Rock & Roll"));
+        out.write_colored(Color::Bad, format_args!("{}", "<3"));
+        out.write_markup(format_args!("<b>{}</b>", "bold"));
+        TrustedMarkup(&mut out).write_colored(Color::Good, format_args!("<i>{}</i>", "italic"));
+        assert_eq!(
+            out.buf,
+            "Rock &amp; Roll\
+             <span color=\"#FF0000\">&lt;3</span>\
+             <b>bold</b>\
+             <span color=\"#00FF00\"><i>italic</i></span>"
+        );
+    }
+}
@@ -1,13 +1,31 @@
 use serde_derive::*;
 
+use super::Color;
 use crate::parse;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(
+    default,
+    bound(deserialize = "C: serde::Deserialize<'de> + StaleColor, GColors<C>: Default")
+)]
 pub struct GColors<C> {
     pub good: C,
     pub mediocre: C,
     pub bad: C,
     pub separator: C,
+    /// Missing in configs written before widgets could time out
+    #[serde(default = "C::stale")]
+    pub stale: C,
+}
+
+pub trait StaleColor {
+    fn stale() -> Self;
+}
+
+impl StaleColor for HexRgb {
+    fn stale() -> Self {
+        "#808080".parse().unwrap()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -68,6 +86,7 @@ enum ColorDef {
 
 // need to duplicate instead of just using GColors because of serde proxy type
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct TerminalColors {
     #[serde(with = "ColorDef")]
     pub good: console::Color,
@@ -79,28 +98,43 @@ pub struct TerminalColors {
     pub separator: console::Color,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
 pub struct ColorCfg {
     pub hex: GColors<HexRgb>,
     pub terminal: TerminalColors,
 }
 
-impl Default for ColorCfg {
+impl Default for GColors<HexRgb> {
+    fn default() -> Self {
+        Self {
+            good: "#00FF00".parse().unwrap(),
+            bad: "#FF0000".parse().unwrap(),
+            mediocre: "#FFFF00".parse().unwrap(),
+            separator: "#333333".parse().unwrap(),
+            stale: HexRgb::stale(),
+        }
+    }
+}
+
+impl GColors<HexRgb> {
+    pub fn get(&self, c: Color) -> &str {
+        match c {
+            Color::Good => self.good.as_ref(),
+            Color::Mediocre => self.mediocre.as_ref(),
+            Color::Bad => self.bad.as_ref(),
+            Color::Stale => self.stale.as_ref(),
+        }
+    }
+}
+
+impl Default for TerminalColors {
     fn default() -> Self {
         Self {
-            hex: GColors {
-                good: "#00FF00".parse().unwrap(),
-                bad: "#FF0000".parse().unwrap(),
-                mediocre: "#FFFF00".parse().unwrap(),
-                separator: "#333333".parse().unwrap(),
-            },
-
-            terminal: TerminalColors {
-                good: console::Color::Green,
-                bad: console::Color::Red,
-                mediocre: console::Color::Yellow,
-                separator: console::Color::Black,
-            },
+            good: console::Color::Green,
+            bad: console::Color::Red,
+            mediocre: console::Color::Yellow,
+            separator: console::Color::Black,
         }
     }
 }
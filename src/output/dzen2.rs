@@ -0,0 +1,118 @@
+use std::fmt::{self, Write};
+
+use super::{
+    color::ColorCfg,
+    escape::{Escape, NEWLINE},
+    tagged::{Syntax, Tagged},
+};
+use crate::output::{Block, Color, Sink};
+
+const SYNTAX: Syntax = Syntax {
+    fg: ("^fg(", ")"),
+    fg_end: "^fg()",
+    escape: |buf, s| Escape(&[('^', "^^"), NEWLINE], buf).write_fmt(s).unwrap(),
+};
+
+#[derive(Debug)]
+pub struct Output {
+    tags: Tagged,
+    sink: Sink,
+}
+
+impl Output {
+    pub fn new(sep: &str, colors: ColorCfg, sink: Sink) -> Self {
+        Self {
+            tags: Tagged::new(sep, colors, &SYNTAX),
+            sink,
+        }
+    }
+}
+
+impl super::Output for Output {
+    fn start(&mut self) {
+        self.tags.buf.clear();
+    }
+
+    fn start_block(&mut self, block: &Block) {
+        let tags = &mut self.tags;
+        tags.clear_block();
+        if let Some(ref background) = block.background {
+            write!(tags.block_start, "^bg({})", background.as_ref()).unwrap();
+        }
+        for action in &block.actions {
+            write!(
+                tags.block_start,
+                "^ca({}, {})",
+                action.button, action.command
+            )
+            .unwrap();
+            tags.block_end.push_str("^ca()");
+        }
+        if block.background.is_some() {
+            tags.block_end.push_str("^bg()");
+        }
+    }
+
+    fn write(&mut self, s: fmt::Arguments) {
+        self.tags.write_text(None, s, false)
+    }
+
+    fn write_sep(&mut self) {
+        self.tags.write_sep();
+    }
+
+    fn write_colored(&mut self, c: Color, s: fmt::Arguments) {
+        self.tags.write_text(Some(c), s, false)
+    }
+
+    fn write_markup(&mut self, s: fmt::Arguments) {
+        self.tags.write_text(None, s, true)
+    }
+
+    fn write_markup_colored(&mut self, c: Color, s: fmt::Arguments) {
+        self.tags.write_text(Some(c), s, true)
+    }
+
+    fn finish(&mut self) {
+        self.sink.write_frame(format_args!("{}", self.tags.buf));
+    }
+
+    fn set_colors(&mut self, colors: &ColorCfg) {
+        self.tags.colors = colors.hex.clone();
+    }
+
+    fn set_sep(&mut self, sep: String) {
+        self.tags.separator = sep;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{Action, Output as _};
+
+    #[test]
+    fn dzen2_commands() {
+        let mut out = Output::new(" | ", ColorCfg::default(), Sink::default());
+        out.start();
+        out.start_block(&Block::default());
+        out.write(format_args!("{}", "x^2^fg(#ff0000)"));
+        out.write_sep();
+        out.start_block(&Block {
+            background: Some("#112233".parse().unwrap()),
+            actions: vec![Action {
+                button: 3,
+                command: "pavucontrol".to_owned(),
+            }],
+            ..Block::default()
+        });
+        out.write_colored(Color::Good, format_args!("vol: {}%", 40));
+
+        assert_eq!(
+            out.tags.buf,
+            "x^^2^^fg(#ff0000)\
+             ^fg(#333333) | ^fg()\
+             ^bg(#112233)^ca(3, pavucontrol)^fg(#00FF00)vol: 40%^fg()^ca()^bg()"
+        );
+    }
+}
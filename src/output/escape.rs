@@ -0,0 +1,47 @@
+use std::fmt::{self, Write};
+
+/// Chars pango would read as markup
+pub const PANGO: &[(char, &str)] = &[
+    ('&', "&amp;"),
+    ('<', "&lt;"),
+    ('>', "&gt;"),
+    ('\'', "&apos;"),
+    ('"', "&quot;"),
+];
+
+/// Bars that read their input line by line would end the frame at a newline
+pub const NEWLINE: (char, &str) = ('\n', " ");
+
+/// Replaces every char of the table that's written with its escape sequence
+pub struct Escape<W>(pub &'static [(char, &'static str)], pub W);
+
+impl<W: Write> Write for Escape<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let table = self.0;
+        let special = |(i, c)| {
+            let (_, with) = table.iter().find(|(special, _)| *special == c)?;
+            Some((i, c, *with))
+        };
+        let mut rest = s;
+        while let Some((i, c, with)) = rest.char_indices().find_map(special) {
+            self.1.write_str(&rest[..i])?;
+            self.1.write_str(with)?;
+            rest = &rest[i + c.len_utf8()..];
+        }
+        self.1.write_str(rest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape() {
+        let mut s = String::new();
+        Escape(PANGO, &mut s)
+            .write_str(r#"Rock & Roll <b>"'</b>"#)
+            .unwrap();
+        assert_eq!(s, "Rock &amp; Roll &lt;b&gt;&quot;&apos;&lt;/b&gt;");
+    }
+}
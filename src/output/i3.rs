@@ -1,22 +1,85 @@
-use std::fmt::{self, Write};
+use std::{
+    fmt::{self, Write},
+    str,
+};
 
 use serde_derive::{Deserialize, Serialize};
 
-use super::color::{ColorCfg, GColors, HexRgb};
-use crate::output::Color;
+use super::{
+    color::{ColorCfg, GColors, HexRgb},
+    escape::{Escape, PANGO},
+};
+use crate::{
+    output::{Block, Color, Markup, Sink},
+    statusbar::{CONT_SIGNAL, STOP_SIGNAL},
+};
 
 pub struct Output {
     buf: String,
+    // serialized fields of the current block without the enclosing braces
+    block: Vec<u8>,
+    // i3bar parses full_text of the current block as pango markup
+    pango: bool,
+    // every status line after the first needs a comma in front of it
+    started: bool,
     cfg: Cfg,
+    sink: Sink,
 }
 
 impl Output {
-    pub fn new(colors: ColorCfg) -> Self {
+    pub fn new(colors: ColorCfg, sink: Sink) -> Self {
         Self {
             buf: String::new(),
+            block: Vec::new(),
+            pango: false,
+            started: false,
             cfg: Cfg { colors: colors.hex },
+            sink,
         }
     }
+
+    fn write_block(&mut self, s: fmt::Arguments, color: Option<Color>, markup: bool) {
+        self.buf.push('{');
+        // serde_json only writes valid utf-8
+        self.buf.push_str(str::from_utf8(&self.block).unwrap());
+        self.buf.push_str(r#""full_text":""#);
+        if self.pango && !markup {
+            Escape(PANGO, JsonEscape(&mut self.buf))
+                .write_fmt(s)
+                .unwrap();
+        } else {
+            JsonEscape(&mut self.buf).write_fmt(s).unwrap();
+        }
+        self.buf.push('"');
+        if let Some(c) = color {
+            // hex colors never need escaping
+            write!(self.buf, r#","color":"{}""#, self.cfg.colors.get(c)).unwrap();
+        }
+        self.buf.push_str("},");
+    }
+}
+
+/// Writes everything as the content of a JSON string
+struct JsonEscape<W>(W);
+
+impl<W: Write> Write for JsonEscape<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut rest = s;
+        while let Some(i) = rest.find(|c: char| c == '"' || c == '\\' || c < ' ') {
+            self.0.write_str(&rest[..i])?;
+            // all escaped chars are ascii
+            match rest.as_bytes()[i] {
+                b'"' => self.0.write_str("\\\"")?,
+                b'\\' => self.0.write_str("\\\\")?,
+                b'\n' => self.0.write_str("\\n")?,
+                b'\r' => self.0.write_str("\\r")?,
+                b'\t' => self.0.write_str("\\t")?,
+                c => write!(self.0, "\\u{:04x}", c)?,
+            }
+            rest = &rest[i + 1..];
+        }
+        self.0.write_str(rest)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,31 +89,50 @@ pub struct Cfg {
 
 impl super::Output for Output {
     fn init(&mut self) {
-        println!(r#"{{"version":1}}"#);
-        println!("[");
+        // i3bar sends the signals when it gets hidden and shown again
+        let header = serde_json::json!({
+            "version": 1,
+            "click_events": true,
+            "stop_signal": STOP_SIGNAL as i32,
+            "cont_signal": CONT_SIGNAL as i32,
+        });
+        self.sink.write_frame(format_args!("{}\n[", header));
     }
 
     fn start(&mut self) {
         self.buf.clear();
-        print!("[");
+    }
+
+    fn start_block(&mut self, block: &Block) {
+        self.pango = block.markup == Some(Markup::Pango);
+        self.block.clear();
+        // actions are for bars that run commands themselves, i3bar sends the clicks to us
+        let block = Block {
+            actions: Vec::new(),
+            ..block.clone()
+        };
+        serde_json::to_writer(&mut self.block, &block).unwrap();
+        self.block.pop();
+        self.block.remove(0);
+        if !self.block.is_empty() {
+            self.block.push(b',');
+        }
     }
 
     fn write(&mut self, s: fmt::Arguments) {
-        write!(self.buf, r#"{{"full_text": "{}"}},"#, s).unwrap()
+        self.write_block(s, None, false)
     }
 
     fn write_colored(&mut self, c: Color, s: fmt::Arguments) {
-        let color = match c {
-            Color::Good => self.cfg.colors.good.as_ref(),
-            Color::Mediocre => self.cfg.colors.mediocre.as_ref(),
-            Color::Bad => self.cfg.colors.bad.as_ref(),
-        };
-        write!(
-            self.buf,
-            r#"{{"full_text": "{}","color": "{}"}},"#,
-            s, color
-        )
-        .unwrap()
+        self.write_block(s, Some(c), false)
+    }
+
+    fn write_markup(&mut self, s: fmt::Arguments) {
+        self.write_block(s, None, true)
+    }
+
+    fn write_markup_colored(&mut self, c: Color, s: fmt::Arguments) {
+        self.write_block(s, Some(c), true)
     }
 
     fn write_sep(&mut self) {}
@@ -59,10 +141,150 @@ impl super::Output for Output {
         if self.buf.ends_with(',') {
             self.buf.pop();
         }
-        println!("{}],", self.buf);
+        let comma = if self.started { "," } else { "" };
+        self.started = true;
+        self.sink
+            .write_frame(format_args!("{}[{}]", comma, self.buf));
+    }
+
+    fn close(&mut self) {
+        self.sink.write_frame(format_args!("]"));
     }
 
     fn set_colors(&mut self, colors: &ColorCfg) {
         self.cfg.colors = colors.hex.clone();
     }
 }
+
+/// A click on a block as sent by i3bar/swaybar on stdin
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClickEvent {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub instance: String,
+    /// 1 to 3 are the left, middle and right button, 4 and 5 scroll up and down
+    pub button: u32,
+}
+
+impl ClickEvent {
+    /// Parses one line of the infinite click event array, returns `None` for lines that only
+    /// contain array syntax
+    pub fn from_line(line: &str) -> Result<Option<Self>, serde_json::Error> {
+        let line = line.trim_start_matches(|c: char| c == '[' || c == ',' || c.is_whitespace());
+        if line.trim_end().is_empty() {
+            Ok(None)
+        } else {
+            serde_json::from_str(line).map(Some)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{Action, Output as _};
+    use formatter::{FormatMap, FormatString};
+
+    const HOSTILE: &[&str] = &[
+        r#"Rock "n" Roll"#,
+        r"C:\Music\",
+        "line\nbreak\ttab\rreturn",
+        "\u{0}nul \u{1b}[31mescape \u{7f}",
+        r##"","color":"#FF0000"},{"full_text":"injected"##,
+        "unicode ♫ \u{2028}",
+    ];
+
+    fn written_blocks(out: &Output) -> Vec<serde_json::Value> {
+        serde_json::from_str(&format!("[{}]", out.buf.trim_end_matches(','))).unwrap()
+    }
+
+    #[test]
+    fn actions_stay_out_of_blocks() {
+        let mut out = Output::new(ColorCfg::default(), Sink::default());
+        out.start_block(&Block {
+            name: "mpd".to_owned(),
+            actions: vec![Action {
+                button: 1,
+                command: "mpc toggle".to_owned(),
+            }],
+            ..Block::default()
+        });
+        out.write(format_args!("{}", "mpd: paused"));
+
+        let blocks = written_blocks(&out);
+        assert_eq!(blocks[0]["name"], "mpd");
+        assert!(blocks[0].get("actions").is_none());
+    }
+
+    #[test]
+    fn hostile_strings_stay_valid_json() {
+        let mut out = Output::new(ColorCfg::default(), Sink::default());
+        out.start_block(&Block {
+            name: "mpd".to_owned(),
+            instance: "\"0\"".to_owned(),
+            short_text: Some("short \\ \"text\"".to_owned()),
+            ..Block::default()
+        });
+
+        // the way widgets write their formatted output
+        let fmt = FormatString::parse("mpd: {title}").unwrap();
+        let mut map = FormatMap::new();
+        for s in HOSTILE {
+            map.update_string_with("title", |title| title.push_str(s));
+            out.write(format_args!("{}", fmt.fmt(&map).unwrap()));
+            out.write_colored(Color::Bad, format_args!("{}", s));
+        }
+
+        let blocks = written_blocks(&out);
+        assert_eq!(blocks.len(), HOSTILE.len() * 2);
+        for (pair, s) in blocks.chunks(2).zip(HOSTILE) {
+            assert_eq!(pair[0]["full_text"], format!("mpd: {}", s));
+            assert_eq!(pair[1]["full_text"], *s);
+            assert_eq!(pair[1]["color"], "#FF0000");
+            for block in pair {
+                assert_eq!(block["instance"], "\"0\"");
+                assert_eq!(block["short_text"], "short \\ \"text\"");
+            }
+        }
+    }
+
+    #[test]
+    fn pango_blocks_escape_plain_text() {
+        let mut out = Output::new(ColorCfg::default(), Sink::default());
+        out.start_block(&Block {
+            markup: Some(Markup::Pango),
+            ..Block::default()
+        });
+        out.write(format_args!("{}", "Rock & Roll"));
+        out.write_markup(format_args!("<b>{}</b>", "\"bold\""));
+
+        let blocks = written_blocks(&out);
+        assert_eq!(blocks[0]["full_text"], "Rock &amp; Roll");
+        assert_eq!(blocks[1]["full_text"], "<b>\"bold\"</b>");
+        assert_eq!(blocks[1]["markup"], "pango");
+    }
+
+    #[test]
+    fn click_event_from_line() {
+        assert!(ClickEvent::from_line("[\n").unwrap().is_none());
+        assert!(ClickEvent::from_line("\n").unwrap().is_none());
+
+        let ev = ClickEvent::from_line(
+            r#"{"name":"mpd","instance":"2","button":1,"modifiers":["Shift"],"x":1200,"y":5,"relative_x":12,"relative_y":5,"width":80,"height":20}"#,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(ev.name, "mpd");
+        assert_eq!(ev.instance, "2");
+        assert_eq!(ev.button, 1);
+
+        // every event after the first one is prefixed with a comma
+        let ev = ClickEvent::from_line(r#",{"name":"datetime","instance":"0","button":4}"#)
+            .unwrap()
+            .unwrap();
+        assert_eq!(ev.button, 4);
+
+        assert!(ClickEvent::from_line(",{\"button\":").is_err());
+    }
+}
@@ -0,0 +1,176 @@
+use std::fmt::{self, Write};
+
+use super::{
+    color::ColorCfg,
+    escape::{Escape, NEWLINE},
+    tagged::{Syntax, Tagged},
+};
+use crate::output::{Align, Block, Color, Sink};
+
+const SYNTAX: Syntax = Syntax {
+    fg: ("%{F", "}"),
+    fg_end: "%{F-}",
+    escape: |buf, s| Escape(&[('%', "%%"), NEWLINE], buf).write_fmt(s).unwrap(),
+};
+
+#[derive(Debug)]
+pub struct Output {
+    tags: Tagged,
+    align: Align,
+    // polybar script modules can't place blocks in different parts of the bar
+    sections: bool,
+    pending_sep: bool,
+    sink: Sink,
+}
+
+impl Output {
+    pub fn new(sep: &str, colors: ColorCfg, sink: Sink) -> Self {
+        Self {
+            tags: Tagged::new(sep, colors, &SYNTAX),
+            align: Align::Left,
+            sections: true,
+            pending_sep: false,
+            sink,
+        }
+    }
+
+    /// Output for polybar's `custom/script` module, which understands the same tags except for
+    /// alignment
+    pub fn polybar(sep: &str, colors: ColorCfg, sink: Sink) -> Self {
+        Self {
+            sections: false,
+            ..Self::new(sep, colors, sink)
+        }
+    }
+
+    fn write_text(&mut self, c: Option<Color>, s: fmt::Arguments, markup: bool) {
+        if self.pending_sep {
+            self.pending_sep = false;
+            self.tags.write_sep();
+        }
+        self.tags.write_text(c, s, markup);
+    }
+}
+
+impl super::Output for Output {
+    fn start(&mut self) {
+        self.tags.buf.clear();
+        self.align = Align::Left;
+        self.pending_sep = false;
+    }
+
+    fn start_block(&mut self, block: &Block) {
+        let align = block.align.unwrap_or(Align::Left);
+        if self.sections && align != self.align {
+            self.align = align;
+            // no separators at the edges of the bar
+            self.pending_sep = false;
+            self.tags.buf.push_str(match align {
+                Align::Left => "%{l}",
+                Align::Center => "%{c}",
+                Align::Right => "%{r}",
+            });
+        }
+
+        let tags = &mut self.tags;
+        tags.clear_block();
+        if let Some(ref background) = block.background {
+            write!(tags.block_start, "%{{B{}}}", background.as_ref()).unwrap();
+        }
+        for action in &block.actions {
+            write!(
+                tags.block_start,
+                "%{{A{}:{}:}}",
+                action.button,
+                action.command.replace(':', "\\:")
+            )
+            .unwrap();
+            tags.block_end.push_str("%{A}");
+        }
+        if block.background.is_some() {
+            tags.block_end.push_str("%{B-}");
+        }
+    }
+
+    fn write(&mut self, s: fmt::Arguments) {
+        self.write_text(None, s, false)
+    }
+
+    fn write_sep(&mut self) {
+        self.pending_sep = true;
+    }
+
+    fn write_colored(&mut self, c: Color, s: fmt::Arguments) {
+        self.write_text(Some(c), s, false)
+    }
+
+    fn write_markup(&mut self, s: fmt::Arguments) {
+        self.write_text(None, s, true)
+    }
+
+    fn write_markup_colored(&mut self, c: Color, s: fmt::Arguments) {
+        self.write_text(Some(c), s, true)
+    }
+
+    fn finish(&mut self) {
+        self.sink.write_frame(format_args!("{}", self.tags.buf));
+    }
+
+    fn set_colors(&mut self, colors: &ColorCfg) {
+        self.tags.colors = colors.hex.clone();
+    }
+
+    fn set_sep(&mut self, sep: String) {
+        self.tags.separator = sep;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{Action, Output as _};
+
+    #[test]
+    fn lemonbar_tags() {
+        let mut out = Output::new(" | ", ColorCfg::default(), Sink::default());
+        out.start();
+        out.start_block(&Block::default());
+        out.write(format_args!("cpu: {}%", 12));
+        out.write_sep();
+        out.start_block(&Block {
+            background: Some("#112233".parse().unwrap()),
+            actions: vec![Action {
+                button: 1,
+                command: "notify-send a:b".to_owned(),
+            }],
+            ..Block::default()
+        });
+        out.write_colored(Color::Bad, format_args!("{}", "50%\nbat"));
+        out.write_sep();
+        out.start_block(&Block {
+            align: Some(Align::Right),
+            ..Block::default()
+        });
+        out.write_markup(format_args!("%{{u#ff0000}}{}", "12:00"));
+
+        assert_eq!(
+            out.tags.buf,
+            "cpu: 12%%\
+             %{F#333333} | %{F-}\
+             %{B#112233}%{A1:notify-send a\\:b:}%{F#FF0000}50%% bat%{F-}%{A}%{B-}\
+             %{r}%{u#ff0000}12:00"
+        );
+    }
+
+    #[test]
+    fn polybar_ignores_align() {
+        let mut out = Output::polybar(" | ", ColorCfg::default(), Sink::default());
+        out.start();
+        out.start_block(&Block {
+            align: Some(Align::Center),
+            ..Block::default()
+        });
+        out.write(format_args!("vol: {}%", 30));
+        assert_eq!(out.tags.buf, "vol: 30%%");
+    }
+}
@@ -0,0 +1,104 @@
+use std::fmt::{self, Write};
+
+use super::{ColorCfg, Output};
+use crate::output::Color;
+
+#[derive(Debug, Clone)]
+enum Op {
+    Text,
+    Colored(Color),
+    Markup,
+    MarkupColored(Color),
+}
+
+/// Remembers what a widget wrote so it can be written again on ticks where the widget doesn't
+/// run
+#[derive(Debug, Clone, Default)]
+pub struct Recording {
+    // all written text, ops point into it by their end
+    text: String,
+    ops: Vec<(Op, usize)>,
+}
+
+impl Recording {
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// Everything written without colors or markup
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn replay(&self, out: &mut dyn Output) {
+        let mut start = 0;
+        for &(ref op, end) in &self.ops {
+            let s = format_args!("{}", &self.text[start..end]);
+            match *op {
+                Op::Text => out.write(s),
+                Op::Colored(c) => out.write_colored(c, s),
+                Op::Markup => out.write_markup(s),
+                Op::MarkupColored(c) => out.write_markup_colored(c, s),
+            }
+            start = end;
+        }
+    }
+
+    /// Replays everything in the stale color
+    pub fn replay_stale(&self, out: &mut dyn Output) {
+        let mut start = 0;
+        for &(ref op, end) in &self.ops {
+            let s = format_args!("{}", &self.text[start..end]);
+            match *op {
+                Op::Text | Op::Colored(_) => out.write_colored(Color::Stale, s),
+                Op::Markup | Op::MarkupColored(_) => out.write_markup_colored(Color::Stale, s),
+            }
+            start = end;
+        }
+    }
+
+    fn record(&mut self, op: Op, s: fmt::Arguments) {
+        self.text.write_fmt(s).unwrap();
+        self.ops.push((op, self.text.len()));
+    }
+}
+
+impl Output for Recording {
+    fn write(&mut self, s: fmt::Arguments) {
+        self.record(Op::Text, s)
+    }
+
+    fn write_colored(&mut self, c: Color, s: fmt::Arguments) {
+        self.record(Op::Colored(c), s)
+    }
+
+    fn write_markup(&mut self, s: fmt::Arguments) {
+        self.record(Op::Markup, s)
+    }
+
+    fn write_markup_colored(&mut self, c: Color, s: fmt::Arguments) {
+        self.record(Op::MarkupColored(c), s)
+    }
+
+    fn set_colors(&mut self, _: &ColorCfg) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_writes_the_same() {
+        let mut rec = Recording::default();
+        rec.write(format_args!("bat: {}", "<50%>"));
+        rec.write_colored(Color::Bad, format_args!(" {}", "discharging"));
+        rec.write(format_args!(""));
+        rec.write_markup_colored(Color::Good, format_args!("<b>{}</b>", 5));
+
+        let mut replayed = Recording::default();
+        rec.replay(&mut replayed);
+        assert_eq!(format!("{:?}", rec), format!("{:?}", replayed));
+        assert_eq!(replayed.text, "bat: <50%> discharging<b>5</b>");
+        assert_eq!(replayed.ops.len(), 4);
+    }
+}
@@ -0,0 +1,262 @@
+use std::{
+    fmt::{self, Write as _},
+    fs::{self, File, OpenOptions},
+    io::{self, Write as _},
+    mem,
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use log::*;
+use nix::{errno::Errno, fcntl::OFlag, sys::stat::Mode, unistd};
+use serde_derive::{Deserialize, Serialize};
+
+/// Where an output writes its frames
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SinkCfg {
+    #[default]
+    Stdout,
+    /// Replaced atomically with the latest frame so readers never see a partial one
+    File { path: PathBuf },
+    /// Created if it doesn't exist, frames get dropped while nobody is reading
+    Fifo { path: PathBuf },
+}
+
+#[derive(Debug)]
+enum Target {
+    Stdout,
+    File {
+        path: PathBuf,
+        tmp: PathBuf,
+    },
+    Fifo {
+        path: PathBuf,
+        pipe: Option<File>,
+        // what's left of a frame that only got written partly
+        rest: Vec<u8>,
+    },
+}
+
+#[derive(Debug)]
+pub struct Sink {
+    target: Target,
+    // every frame needs to be written with a single write
+    buf: String,
+    last: String,
+    last_write: Instant,
+    // unchanged frames still get written after this long
+    redraw: Option<Duration>,
+}
+
+impl Default for Sink {
+    fn default() -> Self {
+        Self::new(&SinkCfg::Stdout, None)
+    }
+}
+
+impl Sink {
+    pub fn new(cfg: &SinkCfg, redraw: Option<Duration>) -> Self {
+        let target = match cfg {
+            SinkCfg::Stdout => Target::Stdout,
+            SinkCfg::File { path } => {
+                // same directory so the rename can't cross filesystems
+                let mut name = std::ffi::OsString::from(".");
+                name.push(path.file_name().unwrap_or_default());
+                name.push(".tmp");
+                Target::File {
+                    path: path.clone(),
+                    tmp: path.with_file_name(name),
+                }
+            }
+            SinkCfg::Fifo { path } => Target::Fifo {
+                path: path.clone(),
+                pipe: None,
+                rest: Vec::new(),
+            },
+        };
+        Self {
+            target,
+            buf: String::new(),
+            last: String::new(),
+            last_write: Instant::now(),
+            redraw,
+        }
+    }
+
+    /// Writes one frame followed by a newline, frames that are the same as the last one are
+    /// skipped so the reader doesn't wake up for nothing
+    pub fn write_frame(&mut self, frame: fmt::Arguments) {
+        self.buf.clear();
+        writeln!(self.buf, "{}", frame).unwrap();
+        let redraw_due = self
+            .redraw
+            .is_some_and(|redraw| self.last_write.elapsed() >= redraw);
+        if self.buf == self.last && !redraw_due {
+            return;
+        }
+
+        let res = match self.target {
+            Target::Stdout => {
+                print!("{}", self.buf);
+                Ok(true)
+            }
+            Target::File { ref path, ref tmp } => write_file(path, tmp, &self.buf).map(|()| true),
+            Target::Fifo {
+                ref path,
+                ref mut pipe,
+                ref mut rest,
+            } => write_fifo(path, pipe, rest, &self.buf),
+        };
+        // frames that didn't arrive get written again even if they don't change
+        match res {
+            Ok(true) => {
+                mem::swap(&mut self.buf, &mut self.last);
+                self.last_write = Instant::now();
+            }
+            Ok(false) => {}
+            Err(e) => warn!("Can't write frame: {}", e),
+        }
+    }
+}
+
+fn write_file(path: &Path, tmp: &Path, frame: &str) -> io::Result<()> {
+    fs::write(tmp, frame)?;
+    fs::rename(tmp, path)
+}
+
+/// Returns whether a reader got the whole frame
+fn write_fifo(
+    path: &Path,
+    pipe: &mut Option<File>,
+    rest: &mut Vec<u8>,
+    frame: &str,
+) -> io::Result<bool> {
+    if pipe.is_none() {
+        // a new reader never saw the start of it
+        rest.clear();
+        if !path.exists() {
+            unistd::mkfifo(path, Mode::S_IRUSR | Mode::S_IWUSR).map_err(io::Error::other)?;
+        }
+        // non-blocking so a missing or slow reader can't stall the bar
+        match OpenOptions::new()
+            .write(true)
+            .custom_flags(OFlag::O_NONBLOCK.bits())
+            .open(path)
+        {
+            Ok(file) => *pipe = Some(file),
+            // nobody is reading
+            Err(ref e) if e.raw_os_error() == Some(Errno::ENXIO as i32) => return Ok(false),
+            Err(e) => return Err(e),
+        }
+    }
+
+    match write_whole(pipe.as_mut().unwrap(), rest, frame.as_bytes()) {
+        // the reader can't keep up
+        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(false),
+        // the reader went away, reopen once there's a new one
+        Err(ref e) if e.kind() == io::ErrorKind::BrokenPipe => {
+            *pipe = None;
+            Ok(false)
+        }
+        res => res,
+    }
+}
+
+/// Frames bigger than `PIPE_BUF` can be written partly, the rest goes first next time so the
+/// reader doesn't get a line made of two frames
+fn write_whole(pipe: &mut File, rest: &mut Vec<u8>, frame: &[u8]) -> io::Result<bool> {
+    while !rest.is_empty() {
+        let len = pipe.write(rest)?;
+        rest.drain(..len);
+    }
+    let len = pipe.write(frame)?;
+    rest.extend_from_slice(&frame[len..]);
+    Ok(rest.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_sink_replaces_frame() {
+        let dir = std::env::temp_dir().join(format!("statusbar-sink-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bar");
+
+        let mut sink = Sink::new(&SinkCfg::File { path: path.clone() }, None);
+        sink.write_frame(format_args!("cpu: {}%", 50));
+        sink.write_frame(format_args!("cpu: {}%", 5));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "cpu: 5%\n");
+        assert!(!dir.join(".bar.tmp").exists());
+
+        // unchanged frames don't touch the file
+        fs::remove_file(&path).unwrap();
+        sink.write_frame(format_args!("cpu: {}%", 5));
+        assert!(!path.exists());
+        sink.redraw = Some(Duration::from_secs(0));
+        sink.write_frame(format_args!("cpu: {}%", 5));
+        assert!(path.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fifo_sink_drops_frames_without_reader() {
+        let dir = std::env::temp_dir().join(format!("statusbar-fifo-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bar");
+
+        let mut sink = Sink::new(&SinkCfg::Fifo { path: path.clone() }, None);
+        sink.write_frame(format_args!("cpu: {}%", 5));
+
+        // read end has to be opened non-blocking too or it waits for a writer
+        let mut reader = OpenOptions::new()
+            .read(true)
+            .custom_flags(OFlag::O_NONBLOCK.bits())
+            .open(&path)
+            .unwrap();
+        // the frame didn't change but the new reader hasn't seen it yet
+        sink.write_frame(format_args!("cpu: {}%", 5));
+        drop(sink);
+        let mut got = String::new();
+        io::Read::read_to_string(&mut reader, &mut got).unwrap();
+        assert_eq!(got, "cpu: 5%\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fifo_sink_finishes_partial_frames() {
+        let dir = std::env::temp_dir().join(format!("statusbar-partial-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bar");
+        let mut sink = Sink::new(&SinkCfg::Fifo { path: path.clone() }, None);
+        unistd::mkfifo(&path, Mode::S_IRUSR | Mode::S_IWUSR).unwrap();
+        let mut reader = OpenOptions::new()
+            .read(true)
+            .custom_flags(OFlag::O_NONBLOCK.bits())
+            .open(&path)
+            .unwrap();
+        let mut got = String::new();
+        let mut read = |got: &mut String| io::Read::read_to_string(&mut reader, got);
+
+        // two of them don't fit into the 64 KiB of the pipe
+        let a = "a".repeat(40_000);
+        let b = "b".repeat(40_000);
+        sink.write_frame(format_args!("{}", a));
+        sink.write_frame(format_args!("{}", b));
+        assert_eq!(sink.last.trim_end(), a);
+
+        // the reader gets the rest of `b` before `b` is written again
+        assert!(read(&mut got).is_err());
+        sink.write_frame(format_args!("{}", b));
+        assert_eq!(sink.last.trim_end(), b);
+        assert!(read(&mut got).is_err());
+        assert_eq!(got.lines().collect::<Vec<_>>(), [&a, &b, &b]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
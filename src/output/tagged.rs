@@ -0,0 +1,76 @@
+//! The shared part of the backends for bars that read formatting tags inline with the text
+
+use std::fmt::{self, Write};
+
+use super::color::{ColorCfg, GColors, HexRgb};
+use crate::output::Color;
+
+/// How a bar spells text colors and keeps plain text from being read as tags
+#[derive(Debug)]
+pub struct Syntax {
+    /// Goes around the hex color to start colored text
+    pub fg: (&'static str, &'static str),
+    pub fg_end: &'static str,
+    pub escape: fn(&mut String, fmt::Arguments),
+}
+
+#[derive(Debug)]
+pub struct Tagged {
+    pub buf: String,
+    // tags wrapped around everything written in the current block
+    pub block_start: String,
+    pub block_end: String,
+    pub separator: String,
+    pub colors: GColors<HexRgb>,
+    syntax: &'static Syntax,
+}
+
+impl Tagged {
+    pub fn new(sep: &str, colors: ColorCfg, syntax: &'static Syntax) -> Self {
+        Self {
+            buf: String::new(),
+            block_start: String::new(),
+            block_end: String::new(),
+            separator: sep.to_owned(),
+            colors: colors.hex,
+            syntax,
+        }
+    }
+
+    pub fn clear_block(&mut self) {
+        self.block_start.clear();
+        self.block_end.clear();
+    }
+
+    pub fn write_text(&mut self, c: Option<Color>, s: fmt::Arguments, markup: bool) {
+        self.buf.push_str(&self.block_start);
+        if let Some(c) = c {
+            let (start, end) = self.syntax.fg;
+            write!(self.buf, "{}{}{}", start, self.colors.get(c), end).unwrap();
+        }
+        if markup {
+            self.buf.write_fmt(s).unwrap();
+        } else {
+            (self.syntax.escape)(&mut self.buf, s);
+        }
+        if c.is_some() {
+            self.buf.push_str(self.syntax.fg_end);
+        }
+        self.buf.push_str(&self.block_end);
+    }
+
+    /// The separator comes from the config so it's written as is, tags and all
+    pub fn write_sep(&mut self) {
+        let (start, end) = self.syntax.fg;
+        write!(
+            self.buf,
+            "{}{}{}{}{}",
+            start,
+            self.colors.separator.as_ref(),
+            end,
+            self.separator,
+            self.syntax.fg_end
+        )
+        .unwrap();
+    }
+}
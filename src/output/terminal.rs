@@ -1,12 +1,13 @@
 use std::fmt::{self, Write};
 
 use super::color::{ColorCfg, TerminalColors};
-use crate::output::Color;
+use crate::output::{Color, Sink};
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct Output {
     buf: String,
     cfg: Cfg,
+    sink: Sink,
 }
 
 #[derive(Debug, Clone)]
@@ -16,13 +17,14 @@ pub struct Cfg {
 }
 
 impl Output {
-    pub fn new(sep: &str, colors: ColorCfg) -> Self {
+    pub fn new(sep: &str, colors: ColorCfg, sink: Sink) -> Self {
         Self {
             buf: String::new(),
             cfg: Cfg {
                 colors: colors.terminal,
                 separator: sep.to_owned(),
             },
+            sink,
         }
     }
 }
@@ -37,12 +39,14 @@ impl super::Output for Output {
     }
 
     fn write_colored(&mut self, c: Color, s: fmt::Arguments) {
-        let color = match c {
-            Color::Good => self.cfg.colors.good,
-            Color::Mediocre => self.cfg.colors.mediocre,
-            Color::Bad => self.cfg.colors.bad,
+        let style = match c {
+            Color::Good => console::Style::new().fg(self.cfg.colors.good),
+            Color::Mediocre => console::Style::new().fg(self.cfg.colors.mediocre),
+            Color::Bad => console::Style::new().fg(self.cfg.colors.bad),
+            // there's no grey in the 8 color palette
+            Color::Stale => console::Style::new().dim(),
         };
-        write!(self.buf, "{}", console::Style::new().fg(color).apply_to(s)).unwrap()
+        write!(self.buf, "{}", style.apply_to(s)).unwrap()
     }
 
     fn write_sep(&mut self) {
@@ -57,7 +61,7 @@ impl super::Output for Output {
     }
 
     fn finish(&mut self) {
-        println!("{}", self.buf);
+        self.sink.write_frame(format_args!("{}", self.buf));
     }
 
     fn set_colors(&mut self, colors: &ColorCfg) {
@@ -0,0 +1,104 @@
+use std::fmt::{self, Write};
+
+use super::{
+    color::ColorCfg,
+    escape::{Escape, NEWLINE},
+    tagged::{Syntax, Tagged},
+};
+use crate::output::{Block, Color, Sink};
+
+const SYNTAX: Syntax = Syntax {
+    fg: ("#[fg=", "]"),
+    fg_end: "#[fg=default]",
+    escape: |buf, s| Escape(&[('#', "##"), NEWLINE], buf).write_fmt(s).unwrap(),
+};
+
+/// Output for tmux's `status-right`/`status-left`, e.g. with a file sink and `#(cat file)`
+#[derive(Debug)]
+pub struct Output {
+    tags: Tagged,
+    sink: Sink,
+}
+
+impl Output {
+    pub fn new(sep: &str, colors: ColorCfg, sink: Sink) -> Self {
+        Self {
+            tags: Tagged::new(sep, colors, &SYNTAX),
+            sink,
+        }
+    }
+}
+
+impl super::Output for Output {
+    fn start(&mut self) {
+        self.tags.buf.clear();
+    }
+
+    fn start_block(&mut self, block: &Block) {
+        let tags = &mut self.tags;
+        tags.clear_block();
+        if let Some(ref background) = block.background {
+            write!(tags.block_start, "#[bg={}]", background.as_ref()).unwrap();
+            tags.block_end.push_str("#[bg=default]");
+        }
+    }
+
+    fn write(&mut self, s: fmt::Arguments) {
+        self.tags.write_text(None, s, false)
+    }
+
+    fn write_sep(&mut self) {
+        self.tags.write_sep();
+    }
+
+    fn write_colored(&mut self, c: Color, s: fmt::Arguments) {
+        self.tags.write_text(Some(c), s, false)
+    }
+
+    fn write_markup(&mut self, s: fmt::Arguments) {
+        self.tags.write_text(None, s, true)
+    }
+
+    fn write_markup_colored(&mut self, c: Color, s: fmt::Arguments) {
+        self.tags.write_text(Some(c), s, true)
+    }
+
+    fn finish(&mut self) {
+        self.sink.write_frame(format_args!("{}", self.tags.buf));
+    }
+
+    fn set_colors(&mut self, colors: &ColorCfg) {
+        self.tags.colors = colors.hex.clone();
+    }
+
+    fn set_sep(&mut self, sep: String) {
+        self.tags.separator = sep;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::Output as _;
+
+    #[test]
+    fn tmux_styles() {
+        let mut out = Output::new(" | ", ColorCfg::default(), Sink::default());
+        out.start();
+        out.start_block(&Block::default());
+        out.write(format_args!("{}", "#[fg=red]#(reboot)\n"));
+        out.write_sep();
+        out.start_block(&Block {
+            background: Some("#112233".parse().unwrap()),
+            ..Block::default()
+        });
+        out.write_colored(Color::Mediocre, format_args!("load: {}", 2.5));
+
+        assert_eq!(
+            out.tags.buf,
+            "##[fg=red]##(reboot) \
+             #[fg=#333333] | #[fg=default]\
+             #[bg=#112233]#[fg=#FFFF00]load: 2.5#[fg=default]#[bg=default]"
+        );
+    }
+}
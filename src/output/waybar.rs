@@ -0,0 +1,204 @@
+use std::fmt::{self, Write};
+
+use formatter::FormatMap;
+use serde_derive::Serialize;
+
+use super::{
+    color::ColorCfg,
+    escape::{Escape, PANGO},
+};
+use crate::output::{Block, Color, Sink};
+
+// widget values waybar gets as `percentage` for its bars and `alt` to pick icons by
+const PERCENTAGE: &[&str] = &["charge", "volume", "percent_used", "usage"];
+const ALT: &[&str] = &["status"];
+
+/// Output for waybar's custom module, colors are left to the stylesheet which gets the worst
+/// color of the frame as `good`, `mediocre`, `bad` or `stale` class. `percentage` and `alt` come
+/// from the first block that has them.
+#[derive(Debug)]
+pub struct Output {
+    text: String,
+    tooltip: String,
+    class: Option<Color>,
+    percentage: Option<u32>,
+    alt: Option<String>,
+    new_block: bool,
+    separator: String,
+    sink: Sink,
+}
+
+#[derive(Serialize)]
+struct Frame<'a> {
+    text: &'a str,
+    tooltip: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    class: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    percentage: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    alt: Option<&'a str>,
+}
+
+impl Output {
+    pub fn new(sep: &str, sink: Sink) -> Self {
+        Self {
+            text: String::new(),
+            tooltip: String::new(),
+            class: None,
+            percentage: None,
+            alt: None,
+            new_block: false,
+            separator: sep.to_owned(),
+            sink,
+        }
+    }
+
+    fn write_text(&mut self, c: Option<Color>, s: fmt::Arguments, markup: bool) {
+        // every block gets its own line in the tooltip
+        if self.new_block {
+            self.new_block = false;
+            if !self.tooltip.is_empty() {
+                self.tooltip.push('\n');
+            }
+        }
+
+        let start = self.text.len();
+        if markup {
+            self.text.write_fmt(s).unwrap();
+        } else {
+            Escape(PANGO, &mut self.text).write_fmt(s).unwrap();
+        }
+        self.tooltip.push_str(&self.text[start..]);
+        self.class = self.class.max(c);
+    }
+
+    fn frame(&self) -> Frame<'_> {
+        Frame {
+            text: &self.text,
+            tooltip: &self.tooltip,
+            class: self.class.map(|c| match c {
+                Color::Good => "good",
+                Color::Mediocre => "mediocre",
+                Color::Bad => "bad",
+                Color::Stale => "stale",
+            }),
+            percentage: self.percentage,
+            alt: self.alt.as_deref(),
+        }
+    }
+}
+
+impl super::Output for Output {
+    fn start(&mut self) {
+        self.text.clear();
+        self.tooltip.clear();
+        self.class = None;
+        self.percentage = None;
+        self.alt = None;
+    }
+
+    fn start_block(&mut self, _: &Block) {
+        self.new_block = true;
+    }
+
+    fn block_values(&mut self, values: &FormatMap) {
+        if self.percentage.is_none() {
+            self.percentage = PERCENTAGE
+                .iter()
+                .find_map(|key| values.get_number(key))
+                .map(|percentage| percentage.round().max(0.) as u32);
+        }
+        if self.alt.is_none() {
+            self.alt = ALT
+                .iter()
+                .find_map(|key| values.get_str(key))
+                .map(str::to_owned);
+        }
+    }
+
+    fn write(&mut self, s: fmt::Arguments) {
+        self.write_text(None, s, false)
+    }
+
+    fn write_sep(&mut self) {
+        self.text.push_str(&self.separator);
+    }
+
+    fn write_colored(&mut self, c: Color, s: fmt::Arguments) {
+        self.write_text(Some(c), s, false)
+    }
+
+    fn write_markup(&mut self, s: fmt::Arguments) {
+        self.write_text(None, s, true)
+    }
+
+    fn write_markup_colored(&mut self, c: Color, s: fmt::Arguments) {
+        self.write_text(Some(c), s, true)
+    }
+
+    fn finish(&mut self) {
+        let frame = serde_json::to_string(&self.frame()).unwrap();
+        self.sink.write_frame(format_args!("{}", frame));
+    }
+
+    fn set_colors(&mut self, _: &ColorCfg) {}
+
+    fn set_sep(&mut self, sep: String) {
+        self.separator = sep;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::Output as _;
+
+    #[test]
+    fn waybar_frame() {
+        let mut volume = FormatMap::new();
+        volume.insert("volume", 39.6);
+        let mut mpd = FormatMap::new();
+        mpd.insert("title", "Rock & Roll".to_owned());
+        let mut battery = FormatMap::new();
+        battery.insert("charge", 80.0);
+        battery.insert("status", "Discharging".to_owned());
+
+        let mut out = Output::new(" | ", Sink::default());
+        out.start();
+        out.start_block(&Block::default());
+        out.block_values(&volume);
+        out.write_colored(Color::Mediocre, format_args!("{}", "vol: muted"));
+        out.write_sep();
+        out.start_block(&Block::default());
+        out.block_values(&mpd);
+        out.write(format_args!("{}", "mpd: Rock & Roll"));
+        out.write_sep();
+        out.start_block(&Block::default());
+        out.block_values(&battery);
+        out.write_colored(Color::Good, format_args!("{}", "bat: 80%"));
+
+        let frame: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&out.frame()).unwrap()).unwrap();
+        assert_eq!(
+            frame["text"],
+            "vol: muted | mpd: Rock &amp; Roll | bat: 80%"
+        );
+        assert_eq!(
+            frame["tooltip"],
+            "vol: muted\nmpd: Rock &amp; Roll\nbat: 80%"
+        );
+        assert_eq!(frame["class"], "mediocre");
+        assert_eq!(frame["percentage"], 40);
+        assert_eq!(frame["alt"], "Discharging");
+
+        // a new frame doesn't keep the values of the last one
+        out.start();
+        out.start_block(&Block::default());
+        out.block_values(&mpd);
+        out.write(format_args!("{}", "mpd: Rock & Roll"));
+        let frame = serde_json::to_value(out.frame()).unwrap();
+        assert!(frame.get("percentage").is_none());
+        assert!(frame.get("alt").is_none());
+    }
+}
@@ -0,0 +1,122 @@
+use std::fmt::{self, Write};
+
+use super::{
+    color::ColorCfg,
+    escape::{Escape, NEWLINE},
+    tagged::{Syntax, Tagged},
+};
+use crate::output::{Block, Color, Sink};
+
+const SYNTAX: Syntax = Syntax {
+    fg: ("<fc=", ">"),
+    fg_end: "</fc>",
+    escape,
+};
+
+fn escape(buf: &mut String, s: fmt::Arguments) {
+    let start = buf.len();
+    Escape(&[NEWLINE], &mut *buf).write_fmt(s).unwrap();
+    if buf[start..].contains('<') {
+        let text = buf.split_off(start);
+        // raw wants the length in chars
+        write!(buf, "<raw={}:{}/>", text.chars().count(), text).unwrap();
+    }
+}
+
+/// Output for xmobar's `UnsafeStdinReader`
+#[derive(Debug)]
+pub struct Output {
+    tags: Tagged,
+    sink: Sink,
+}
+
+impl Output {
+    pub fn new(sep: &str, colors: ColorCfg, sink: Sink) -> Self {
+        Self {
+            tags: Tagged::new(sep, colors, &SYNTAX),
+            sink,
+        }
+    }
+}
+
+impl super::Output for Output {
+    fn start(&mut self) {
+        self.tags.buf.clear();
+    }
+
+    fn start_block(&mut self, block: &Block) {
+        let tags = &mut self.tags;
+        tags.clear_block();
+        for action in &block.actions {
+            write!(
+                tags.block_start,
+                "<action=`{}` button={}>",
+                action.command, action.button
+            )
+            .unwrap();
+            tags.block_end.push_str("</action>");
+        }
+    }
+
+    fn write(&mut self, s: fmt::Arguments) {
+        self.tags.write_text(None, s, false)
+    }
+
+    fn write_sep(&mut self) {
+        self.tags.write_sep();
+    }
+
+    fn write_colored(&mut self, c: Color, s: fmt::Arguments) {
+        self.tags.write_text(Some(c), s, false)
+    }
+
+    fn write_markup(&mut self, s: fmt::Arguments) {
+        self.tags.write_text(None, s, true)
+    }
+
+    fn write_markup_colored(&mut self, c: Color, s: fmt::Arguments) {
+        self.tags.write_text(Some(c), s, true)
+    }
+
+    fn finish(&mut self) {
+        self.sink.write_frame(format_args!("{}", self.tags.buf));
+    }
+
+    fn set_colors(&mut self, colors: &ColorCfg) {
+        self.tags.colors = colors.hex.clone();
+    }
+
+    fn set_sep(&mut self, sep: String) {
+        self.tags.separator = sep;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{Action, Output as _};
+
+    #[test]
+    fn xmobar_tags() {
+        let mut out = Output::new(" | ", ColorCfg::default(), Sink::default());
+        out.start();
+        out.start_block(&Block {
+            actions: vec![Action {
+                button: 1,
+                command: "mpc toggle".to_owned(),
+            }],
+            ..Block::default()
+        });
+        out.write(format_args!("mpd: {}", "<fc=#ff0000>♫\nx"));
+        out.write_sep();
+        out.start_block(&Block::default());
+        out.write_colored(Color::Bad, format_args!("bat: {}%", 5));
+
+        assert_eq!(
+            out.tags.buf,
+            "<action=`mpc toggle` button=1><raw=20:mpd: <fc=#ff0000>♫ x/></action>\
+             <fc=#333333> | </fc>\
+             <fc=#FF0000>bat: 5%</fc>"
+        );
+    }
+}
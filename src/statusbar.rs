@@ -1,4 +1,11 @@
+mod alert;
+mod control;
+mod notify;
 mod status;
 mod system;
 
-pub use self::system::run;
+pub use self::{
+    alert::{AlertCfg, Limit},
+    control::ctl,
+    system::{run, CONT_SIGNAL, STOP_SIGNAL},
+};
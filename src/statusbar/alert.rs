@@ -0,0 +1,140 @@
+use std::collections::BTreeMap;
+
+use failure::format_err;
+use formatter::{FormatMap, FormatString};
+use serde_derive::{Deserialize, Serialize};
+
+/// Notifies when a value of a widget crosses a limit
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AlertCfg {
+    /// Key of the value, the same as in the format of the widget
+    pub key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub above: Option<Limit>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub below: Option<Limit>,
+    /// String values that have to match for the alert to fire, e.g. `status = "Discharging"`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub when: BTreeMap<String, String>,
+    /// How far the value has to get back from the limit before the alert can fire again
+    #[serde(default)]
+    pub hysteresis: f64,
+    /// Body of the notification, can use every key of the widget
+    pub message: String,
+}
+
+/// Either a number or the key of another value of the widget, e.g. `temp_crit`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Limit {
+    Value(f64),
+    Key(String),
+}
+
+impl Limit {
+    fn get(&self, values: &FormatMap) -> Option<f64> {
+        match self {
+            Limit::Value(n) => Some(*n),
+            Limit::Key(key) => values.get_number(key),
+        }
+    }
+}
+
+pub struct Alert {
+    key: String,
+    above: Option<Limit>,
+    below: Option<Limit>,
+    when: BTreeMap<String, String>,
+    hysteresis: f64,
+    message: FormatString,
+    // fired and the value hasn't come back yet
+    active: bool,
+}
+
+impl Alert {
+    pub fn new(cfg: AlertCfg) -> Result<Self, failure::Error> {
+        if cfg.above.is_none() && cfg.below.is_none() {
+            return Err(format_err!(
+                "Alert on {} needs a limit in `above` or `below`",
+                cfg.key
+            ));
+        }
+        Ok(Self {
+            message: FormatString::parse(&cfg.message)?,
+            key: cfg.key,
+            above: cfg.above,
+            below: cfg.below,
+            when: cfg.when,
+            hysteresis: cfg.hysteresis,
+            active: false,
+        })
+    }
+
+    /// Returns the message once per crossing of the limit
+    pub fn check(&mut self, values: &FormatMap) -> Option<String> {
+        let value = values.get_number(&self.key)?;
+        let matches = self
+            .when
+            .iter()
+            .all(|(key, expected)| values.get_str(key) == Some(expected));
+        let above = self.above.as_ref().and_then(|limit| limit.get(values));
+        let below = self.below.as_ref().and_then(|limit| limit.get(values));
+
+        if self.active {
+            let back = !matches
+                || (above.is_none_or(|limit| value <= limit - self.hysteresis)
+                    && below.is_none_or(|limit| value >= limit + self.hysteresis));
+            if back {
+                self.active = false;
+            }
+            None
+        } else if matches
+            && (above.is_some_and(|limit| value > limit)
+                || below.is_some_and(|limit| value < limit))
+        {
+            self.active = true;
+            // keys used in the message aren't checked when the config is loaded
+            Some(match self.message.fmt(values) {
+                Ok(message) => message.to_string(),
+                Err(e) => format!("{} ({})", self.key, e),
+            })
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alert_fires_once_per_crossing() {
+        let cfg: AlertCfg = toml::from_str(
+            r#"
+key = "charge"
+below = 10
+hysteresis = 2
+message = "Battery at {charge:.0}%"
+when = { status = "Discharging" }
+"#,
+        )
+        .unwrap();
+        let mut alert = Alert::new(cfg).unwrap();
+
+        let mut values = FormatMap::new();
+        let mut check = |charge: f64, status: &str| {
+            values.insert("charge", charge);
+            values.update_string_with("status", |s| s.push_str(status));
+            alert.check(&values)
+        };
+        assert_eq!(check(9., "Charging"), None);
+        assert_eq!(check(9., "Discharging").unwrap(), "Battery at 9%");
+        assert_eq!(check(8., "Discharging"), None);
+        // within the hysteresis
+        assert_eq!(check(11., "Discharging"), None);
+        assert_eq!(check(9., "Discharging"), None);
+        assert_eq!(check(12., "Discharging"), None);
+        assert!(check(9., "Discharging").is_some());
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::Duration,
+};
+
+use actix::prelude::{Actor, Addr, SyncContext};
+use directories::ProjectDirs;
+use failure::{format_err, Fail, ResultExt};
+use log::*;
+
+use super::system::{format_error, Bar, Control, ErrorLog, Listening};
+use crate::config::Format;
+
+/// Commands accepted on the control socket, one per connection
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// Run all widgets now
+    Refresh,
+    Reload,
+    Hide(String),
+    Show(String),
+    /// What every widget shows as JSON
+    Dump,
+    /// Replaces the outputs like `--format`
+    SetFormat(Format),
+}
+
+impl std::str::FromStr for Command {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let words = s.split_whitespace().collect::<Vec<_>>();
+        match words[..] {
+            ["refresh"] => Ok(Command::Refresh),
+            ["reload"] => Ok(Command::Reload),
+            ["hide", widget] => Ok(Command::Hide(widget.to_owned())),
+            ["show", widget] => Ok(Command::Show(widget.to_owned())),
+            ["dump"] => Ok(Command::Dump),
+            ["set-format", format] => Ok(Command::SetFormat(format.parse()?)),
+            _ => Err(format_err!(
+                "Invalid command: {}, accepted commands: refresh, reload, hide <widget>, \
+                 show <widget>, dump, set-format <format>",
+                s
+            )),
+        }
+    }
+}
+
+/// `$XDG_RUNTIME_DIR/statusbar-rs/ctl.sock`, bars with a named config get `ctl-<name>.sock`
+pub fn socket_path(name: Option<&str>) -> Option<PathBuf> {
+    let file = match name {
+        Some(name) => format!("ctl-{}.sock", name),
+        None => "ctl.sock".to_owned(),
+    };
+    ProjectDirs::from("com", "foldu", "statusbar-rs")?
+        .runtime_dir()
+        .map(|dir| dir.join(file))
+}
+
+/// Sends a command to the running bar with that config name and prints the reply
+pub fn ctl(command: &[String], name: Option<&str>) -> Result<(), failure::Error> {
+    let path = socket_path(name).ok_or_else(|| format_err!("XDG_RUNTIME_DIR isn't set"))?;
+    let mut stream = UnixStream::connect(&path)
+        .with_context(|_| format!("Can't connect to bar on {}", path.display()))?;
+    writeln!(stream, "{}", command.join(" "))?;
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply)?;
+    if let Some(e) = reply.strip_prefix("error: ") {
+        return Err(format_err!("{}", e.trim_end()));
+    }
+    print!("{}", reply);
+    Ok(())
+}
+
+fn bind(path: &Path) -> io::Result<UnixListener> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    match UnixListener::bind(path) {
+        Err(ref e) if e.kind() == io::ErrorKind::AddrInUse => {
+            if UnixStream::connect(path).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    "another bar is already listening",
+                ));
+            }
+            // left behind by a bar that didn't exit cleanly
+            fs::remove_file(path)?;
+            UnixListener::bind(path)
+        }
+        res => res,
+    }
+}
+
+pub struct ControlSocket {
+    pub path: PathBuf,
+    pub tx: Addr<Bar>,
+}
+
+impl ControlSocket {
+    fn serve(&self, stream: UnixStream) -> Result<(), failure::Error> {
+        // a client that doesn't send anything can't keep others waiting
+        stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+        let mut line = String::new();
+        BufReader::new(&stream).read_line(&mut line)?;
+
+        let reply = match line.parse() {
+            Ok(cmd) => {
+                let (tx, rx) = mpsc::channel();
+                self.tx.do_send(Control(cmd, tx));
+                rx.recv()
+                    .unwrap_or_else(|_| Err(format_err!("Bar didn't reply")))
+            }
+            Err(e) => Err(e),
+        };
+        let mut stream = &stream;
+        match reply {
+            Ok(ref reply) if reply.is_empty() => {}
+            Ok(reply) => writeln!(stream, "{}", reply)?,
+            Err(e) => writeln!(stream, "error: {}", format_error(&e))?,
+        }
+        Ok(())
+    }
+}
+
+impl Actor for ControlSocket {
+    type Context = SyncContext<Self>;
+    fn started(&mut self, _ctx: &mut Self::Context) {
+        let listener = match bind(&self.path) {
+            Ok(listener) => listener,
+            Err(e) => {
+                let e = e.context(format!("Can't listen on {}", self.path.display()));
+                self.tx.do_send(ErrorLog(e.into()));
+                return;
+            }
+        };
+        // only a bar that got the socket may remove it
+        self.tx.do_send(Listening(self.path.clone()));
+        for stream in listener.incoming() {
+            let res = stream
+                .map_err(Into::into)
+                .and_then(|stream| self.serve(stream));
+            if let Err(e) = res {
+                warn!("Control connection failed: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!("refresh\n".parse::<Command>().unwrap(), Command::Refresh);
+        assert_eq!(
+            "hide  volume".parse::<Command>().unwrap(),
+            Command::Hide("volume".to_owned())
+        );
+        assert_eq!(
+            "set-format tmux".parse::<Command>().unwrap(),
+            Command::SetFormat(Format::Tmux)
+        );
+        assert!("set-format vga".parse::<Command>().is_err());
+        assert!("show".parse::<Command>().is_err());
+        assert!("dump all".parse::<Command>().is_err());
+        let e = " frobnicate\n".parse::<Command>().unwrap_err();
+        assert!(e.to_string().starts_with("Invalid command: frobnicate, "));
+    }
+}
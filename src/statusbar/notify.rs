@@ -0,0 +1,189 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use failure::format_err;
+use log::*;
+use notify_rust::Notification;
+
+use super::system::format_error;
+
+/// Shows desktop notifications
+pub trait Notifier {
+    fn notify(&mut self, summary: &str, body: &str) -> Result<(), failure::Error>;
+}
+
+/// Sends notifications to the notification server on the session bus
+pub struct Desktop;
+
+impl Notifier for Desktop {
+    fn notify(&mut self, summary: &str, body: &str) -> Result<(), failure::Error> {
+        Notification::new()
+            .summary(summary)
+            .body(body)
+            .show()
+            .map(|_| ())
+            .map_err(|e| format_err!("Can't show notification: {}", e))
+    }
+}
+
+/// Drops all notifications, for runs that don't stay around to show them
+pub struct Silent;
+
+impl Notifier for Silent {
+    fn notify(&mut self, _: &str, _: &str) -> Result<(), failure::Error> {
+        Ok(())
+    }
+}
+
+/// Notifies every error of a widget at most once per cooldown and tells when the widget works
+/// again
+pub struct Notifications {
+    notifier: Box<dyn Notifier>,
+    cooldown: Duration,
+    // keyed by widget and error message, errors that don't come from a widget use an empty name
+    sent: HashMap<(String, String), Instant>,
+    // widgets with notified errors that haven't worked since
+    failing: HashSet<String>,
+}
+
+impl Notifications {
+    pub fn new(notifier: Box<dyn Notifier>, cooldown: Duration) -> Self {
+        Self {
+            notifier,
+            cooldown,
+            sent: HashMap::new(),
+            failing: HashSet::new(),
+        }
+    }
+
+    pub fn set_cooldown(&mut self, cooldown: Duration) {
+        self.cooldown = cooldown;
+    }
+
+    pub fn error(&mut self, widget: Option<&str>, err: &failure::Error) {
+        let now = Instant::now();
+        let cooldown = self.cooldown;
+        self.sent
+            .retain(|_, sent| now.duration_since(*sent) < cooldown);
+        let key = (widget.unwrap_or_default().to_owned(), err.to_string());
+        if self.sent.contains_key(&key) {
+            return;
+        }
+
+        let summary = match widget {
+            Some(widget) => format!("statusbar-rs: {} failed", widget),
+            None => "statusbar-rs error".to_owned(),
+        };
+        self.send(&summary, &format_error(err));
+        self.sent.insert(key, now);
+        if let Some(widget) = widget {
+            self.failing.insert(widget.to_owned());
+        }
+    }
+
+    /// Called after a widget ran successfully
+    pub fn resolved(&mut self, widget: &str) {
+        // errors stay in `sent` so a flapping widget can't get around the cooldown
+        if self.failing.remove(widget) {
+            self.send(&format!("statusbar-rs: {} works again", widget), "");
+        }
+    }
+
+    /// Alerts fire once per crossing so they aren't limited
+    pub fn alert(&mut self, widget: &str, message: &str) {
+        self.send(&format!("statusbar-rs: {}", widget), message);
+    }
+
+    fn send(&mut self, summary: &str, body: &str) {
+        if let Err(e) = self.notifier.notify(summary, body) {
+            warn!("{}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use failure::ResultExt;
+    use std::{cell::RefCell, rc::Rc};
+
+    // what got sent as (summary, body)
+    type Sent = Rc<RefCell<Vec<(String, String)>>>;
+
+    struct Stub(Sent);
+
+    impl Notifier for Stub {
+        fn notify(&mut self, summary: &str, body: &str) -> Result<(), failure::Error> {
+            self.0
+                .borrow_mut()
+                .push((summary.to_owned(), body.to_owned()));
+            Ok(())
+        }
+    }
+
+    fn summaries(sent: &Sent) -> Vec<String> {
+        sent.borrow()
+            .iter()
+            .map(|(summary, _)| summary.clone())
+            .collect()
+    }
+
+    #[test]
+    fn notifications_are_deduplicated() {
+        let sent = Sent::default();
+        let mut notifications =
+            Notifications::new(Box::new(Stub(sent.clone())), Duration::from_secs(60));
+
+        notifications.error(Some("battery"), &format_err!("No battery"));
+        notifications.error(Some("battery"), &format_err!("No battery"));
+        notifications.error(Some("mpd"), &format_err!("No battery"));
+        notifications.error(Some("battery"), &format_err!("Can't read uevent"));
+        notifications.resolved("battery");
+        notifications.resolved("battery");
+        notifications.error(Some("battery"), &format_err!("No battery"));
+        assert_eq!(
+            summaries(&sent),
+            [
+                "statusbar-rs: battery failed",
+                "statusbar-rs: mpd failed",
+                "statusbar-rs: battery failed",
+                "statusbar-rs: battery works again",
+            ]
+        );
+
+        notifications.set_cooldown(Duration::from_secs(0));
+        notifications.error(Some("battery"), &format_err!("No battery"));
+        assert_eq!(sent.borrow().len(), 5);
+    }
+
+    #[test]
+    fn notifications_have_causes_and_messages() {
+        let sent = Sent::default();
+        let mut notifications =
+            Notifications::new(Box::new(Stub(sent.clone())), Duration::from_secs(60));
+
+        let e = Err::<(), _>(format_err!("No such file"))
+            .context("Can't read uevent")
+            .unwrap_err();
+        notifications.error(Some("battery"), &e.into());
+        notifications.error(None, &format_err!("Can't reload config"));
+        notifications.alert("battery", "Battery at 5%");
+        notifications.resolved("battery");
+
+        let note = |summary: &str, body: &str| (summary.to_owned(), body.to_owned());
+        assert_eq!(
+            *sent.borrow(),
+            [
+                note(
+                    "statusbar-rs: battery failed",
+                    "Can't read uevent\nNo such file"
+                ),
+                note("statusbar-rs error", "Can't reload config"),
+                note("statusbar-rs: battery", "Battery at 5%"),
+                note("statusbar-rs: battery works again", ""),
+            ]
+        );
+    }
+}
@@ -1,16 +1,201 @@
+use std::{
+    any::Any,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
 use actix::prelude::Addr;
+use failure::format_err;
+use formatter::{FormatMap, FormatString};
+use log::*;
+use serde_derive::Serialize;
 
-use super::system::{Bar, ErrorLog};
+use super::{
+    alert::Alert,
+    system::{format_error, Alerted, Bar, Recovered, Wake, WidgetError},
+};
 use crate::{
     config::{Config, GeneralCfg},
-    output::Output,
-    widget::{widget_from_kind, Widget},
+    output::{Block, ClickEvent, Color, Markup, Output, Recording, TrustedMarkup},
+    widget::{widget_from_kind, Cancel, Canceller, Widget, WidgetCfg},
 };
 
+const DEFAULT_KEEP_LAST: u32 = 3;
+
+struct Slot {
+    block: Block,
+    // None while the widget runs on the thread pool
+    widget: Option<Box<dyn Widget>>,
+    interval: Duration,
+    timeout: Duration,
+    due: Instant,
+    // what the widget wrote the last time it ran
+    last: Recording,
+    // the last run took too long so `last` is outdated
+    stale: bool,
+    keep_last: u32,
+    format_error: FormatString,
+    fmt_map: FormatMap,
+    // what the widget computed in its last successful run
+    values: FormatMap,
+    // failed runs since the last successful one
+    failures: u32,
+    // repeats of the last logged error aren't logged again
+    last_error: Option<String>,
+    alerts: Vec<Alert>,
+    // hidden from the control socket
+    hidden: bool,
+}
+
+/// A widget coming back from the thread pool
+struct Done {
+    index: usize,
+    widget: Box<dyn Widget>,
+    written: Recording,
+    res: Result<(), failure::Error>,
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s
+    } else {
+        "unknown cause"
+    }
+}
+
+fn millis(ms: u32) -> Duration {
+    Duration::from_millis(u64::from(ms))
+}
+
+impl Slot {
+    fn new(index: usize, cfg: WidgetCfg, general: &GeneralCfg) -> Result<Self, failure::Error> {
+        let name = cfg.kind.name();
+        let mut block = cfg.block.unwrap_or_default();
+        if block.name.is_empty() {
+            block.name = name.to_owned();
+        }
+        if block.instance.is_empty() {
+            block.instance = index.to_string();
+        }
+        let format_error = cfg.format_error.unwrap_or_else(|| format!("{}: ERR", name));
+        Ok(Self {
+            block,
+            widget: Some(widget_from_kind(cfg.kind)?),
+            interval: millis(cfg.interval.unwrap_or(general.update_interval)),
+            timeout: millis(cfg.timeout.unwrap_or(general.update_interval)),
+            due: Instant::now(),
+            last: Recording::default(),
+            stale: false,
+            keep_last: cfg.keep_last.unwrap_or(DEFAULT_KEEP_LAST),
+            format_error: FormatString::parse_with_allowed_keys(&format_error, &["error"])?,
+            fmt_map: FormatMap::new(),
+            values: FormatMap::new(),
+            failures: 0,
+            last_error: None,
+            alerts: cfg
+                .alerts
+                .into_iter()
+                .map(Alert::new)
+                .collect::<Result<_, _>>()?,
+            hidden: false,
+        })
+    }
+
+    /// Runs the widget on the thread pool if it isn't still busy with its last run, returns when
+    /// it has to be done
+    fn spawn(&mut self, index: usize, tx: &Sender<Done>) -> Option<Instant> {
+        // a widget that hangs must not stay due or the bar would redraw in a busy loop
+        let now = Instant::now();
+        self.due = now + self.interval;
+        let mut widget = self.widget.take()?;
+        // the user promised their format strings are markup
+        let markup = self.block.markup == Some(Markup::Pango);
+        let tx = tx.clone();
+        rayon::spawn(move || {
+            let mut written = Recording::default();
+            // a panic on the thread pool would abort the entire bar
+            let res = panic::catch_unwind(AssertUnwindSafe(|| {
+                if markup {
+                    widget.run(&mut TrustedMarkup(&mut written))
+                } else {
+                    widget.run(&mut written)
+                }
+            }))
+            .unwrap_or_else(|payload| Err(format_err!("Panicked: {}", panic_message(&*payload))));
+            // the statusbar is gone if this fails
+            let _ = tx.send(Done {
+                index,
+                widget,
+                written,
+                res,
+            });
+        });
+        Some(now + self.timeout)
+    }
+
+    /// Takes the result of a run, returns the error if it should be logged
+    fn finish(
+        &mut self,
+        written: Recording,
+        res: Result<(), failure::Error>,
+    ) -> Option<failure::Error> {
+        self.stale = false;
+        let e = match res {
+            Ok(()) => {
+                self.last = written;
+                self.failures = 0;
+                self.last_error = None;
+                return None;
+            }
+            Err(e) => e,
+        };
+
+        // whatever got written before the error is incomplete
+        self.failures += 1;
+        if self.failures > self.keep_last || self.last.is_empty() {
+            self.fmt_map
+                .update_string_with("error", |s| s.push_str(&e.to_string()));
+            self.last = Recording::default();
+            if let Ok(s) = self.format_error.fmt(&self.fmt_map) {
+                self.last.write_colored(Color::Bad, format_args!("{}", s));
+            }
+        }
+
+        let msg = format_error(&e);
+        if self.last_error.as_ref() == Some(&msg) {
+            debug!("Widget {} failed again: {}", self.block.name, e);
+            None
+        } else {
+            self.last_error = Some(msg);
+            Some(e)
+        }
+    }
+}
+
 pub struct Statusbar {
-    widgets: Vec<Box<dyn Widget>>,
+    widgets: Vec<Slot>,
     general_cfg: GeneralCfg,
     controller: Addr<Bar>,
+    // tells wakeup threads that their widgets are gone
+    alive: Arc<AtomicBool>,
+    // stops the wakeup threads when dropped
+    canceller: Option<Canceller>,
+    tx: Sender<Done>,
+    rx: Receiver<Done>,
+}
+
+impl Drop for Statusbar {
+    fn drop(&mut self) {
+        self.alive.store(false, Ordering::Relaxed);
+    }
 }
 
 impl Statusbar {
@@ -22,31 +207,248 @@ impl Statusbar {
     ) -> Result<Self, failure::Error> {
         let widgets = widgets
             .into_iter()
-            .map(widget_from_kind)
+            .enumerate()
+            .map(|(i, cfg)| Slot::new(i, cfg, &general))
             .collect::<Result<_, _>>()?;
 
-        Ok(Self {
+        Ok(Self::with_slots(widgets, general, controller))
+    }
+
+    fn with_slots(widgets: Vec<Slot>, general_cfg: GeneralCfg, controller: Addr<Bar>) -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self {
             widgets,
-            general_cfg: general,
+            general_cfg,
             controller,
-        })
+            alive: Arc::new(AtomicBool::new(true)),
+            canceller: None,
+            tx,
+            rx,
+        }
     }
 
+    /// Starts the threads of widgets that can tell when they have something new, runs that only
+    /// render one frame don't need them
+    pub fn spawn_wakeups(&mut self) {
+        let cancel = match Cancel::new() {
+            Ok((canceller, cancel)) => {
+                self.canceller = Some(canceller);
+                Arc::new(cancel)
+            }
+            Err(e) => {
+                warn!(
+                    "Can't create pipe, widgets only update in their interval: {}",
+                    e
+                );
+                return;
+            }
+        };
+        for (i, slot) in self.widgets.iter().enumerate() {
+            if let Some(wakeup) = slot.widget.as_ref().and_then(|widget| widget.wakeup()) {
+                let alive = self.alive.clone();
+                let cancel = cancel.clone();
+                let tx = self.controller.clone();
+                let name = slot.block.name.clone();
+                thread::spawn(move || {
+                    let res = wakeup.run(&cancel, &mut || {
+                        let alive = alive.load(Ordering::Relaxed);
+                        if alive {
+                            tx.do_send(Wake(i));
+                        }
+                        alive
+                    });
+                    if let Err(e) = res {
+                        if alive.load(Ordering::Relaxed) {
+                            tx.do_send(WidgetError(name, e));
+                        }
+                    }
+                });
+            }
+        }
+    }
+
+    /// Runs all widgets that are due in parallel and writes what every widget wrote the last time
+    /// it ran, widgets that take longer than their timeout are shown as stale
     pub fn update(&mut self, out: &mut dyn Output) {
+        // widgets that timed out earlier
+        while let Ok(done) = self.rx.try_recv() {
+            self.finish_run(done);
+        }
+
+        let now = Instant::now();
+        let mut pending = Vec::new();
+        for (i, slot) in self.widgets.iter_mut().enumerate() {
+            if slot.due <= now {
+                if let Some(deadline) = slot.spawn(i, &self.tx) {
+                    pending.push((i, deadline));
+                }
+            }
+        }
+
+        while let Some(&(_, deadline)) = pending.iter().min_by_key(|(_, deadline)| *deadline) {
+            match self
+                .rx
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            {
+                Ok(done) => {
+                    pending.retain(|&(i, _)| i != done.index);
+                    self.finish_run(done);
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    let now = Instant::now();
+                    for &(i, _) in pending.iter().filter(|(_, deadline)| *deadline <= now) {
+                        let slot = &mut self.widgets[i];
+                        slot.stale = true;
+                        self.controller.do_send(WidgetError(
+                            slot.block.name.clone(),
+                            format_err!("Timed out after {:?}", slot.timeout),
+                        ));
+                    }
+                    pending.retain(|&(_, deadline)| deadline > now);
+                }
+                // can't happen because we hold a sender
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+
         out.start();
-        for (i, widget) in self.widgets.iter_mut().enumerate() {
-            if i != 0 {
+        let mut first = true;
+        for slot in &self.widgets {
+            // no separator for widgets that have nothing to show
+            if slot.hidden || slot.last.is_empty() {
+                continue;
+            }
+            if !first {
                 out.write_sep();
             }
-            if let Err(e) = widget.run(out) {
-                self.controller.do_send(ErrorLog(e));
+            first = false;
+            out.start_block(&slot.block);
+            out.block_values(&slot.values);
+            if slot.stale {
+                slot.last.replay_stale(out);
+            } else {
+                slot.last.replay(out);
             }
         }
         out.finish();
     }
 
-    pub fn update_interval(&self) -> u32 {
-        self.general_cfg.update_interval
+    fn finish_run(&mut self, done: Done) {
+        let slot = &mut self.widgets[done.index];
+        if done.res.is_ok() {
+            if slot.failures > 0 || slot.stale {
+                self.controller.do_send(Recovered(slot.block.name.clone()));
+            }
+            if let Some(values) = done.widget.values() {
+                slot.values = values.clone();
+                for alert in &mut slot.alerts {
+                    if let Some(message) = alert.check(values) {
+                        self.controller
+                            .do_send(Alerted(slot.block.name.clone(), message));
+                    }
+                }
+            }
+        }
+        slot.widget = Some(done.widget);
+        if let Some(e) = slot.finish(done.written, done.res) {
+            self.controller
+                .do_send(WidgetError(slot.block.name.clone(), e));
+        }
+    }
+
+    /// Some widget failed or timed out in its last run
+    pub fn failed(&self) -> bool {
+        self.widgets
+            .iter()
+            .any(|slot| slot.failures > 0 || slot.stale)
+    }
+
+    /// Makes all widgets run with the next update
+    pub fn refresh(&mut self) {
+        let now = Instant::now();
+        for slot in &mut self.widgets {
+            slot.due = now;
+        }
+    }
+
+    /// Hides or shows all widgets with that block name
+    pub fn set_hidden(&mut self, name: &str, hidden: bool) -> Result<(), failure::Error> {
+        let mut found = false;
+        for slot in self
+            .widgets
+            .iter_mut()
+            .filter(|slot| slot.block.name == name)
+        {
+            slot.hidden = hidden;
+            found = true;
+        }
+        if found {
+            Ok(())
+        } else {
+            Err(format_err!("No widget named {}", name))
+        }
+    }
+
+    /// What every widget shows right now as JSON
+    pub fn dump(&self) -> String {
+        #[derive(Serialize)]
+        struct Dumped<'a> {
+            name: &'a str,
+            instance: &'a str,
+            text: &'a str,
+            stale: bool,
+            hidden: bool,
+            failures: u32,
+        }
+
+        let dumped = self
+            .widgets
+            .iter()
+            .map(|slot| Dumped {
+                name: &slot.block.name,
+                instance: &slot.block.instance,
+                text: slot.last.text(),
+                stale: slot.stale,
+                hidden: slot.hidden,
+                failures: slot.failures,
+            })
+            .collect::<Vec<_>>();
+        serde_json::to_string(&dumped).unwrap()
+    }
+
+    /// Makes the widget at `index` run with the next update
+    pub fn wake(&mut self, index: usize) {
+        if let Some(slot) = self.widgets.get_mut(index) {
+            slot.due = Instant::now();
+        }
+    }
+
+    /// Time until the next widget is due
+    pub fn next_update(&self) -> Duration {
+        self.widgets
+            .iter()
+            .map(|slot| slot.due)
+            .min()
+            .map(|due| due.saturating_duration_since(Instant::now()))
+            .unwrap_or_else(|| Duration::from_millis(u64::from(self.general_cfg.update_interval)))
+    }
+
+    pub fn click(&mut self, ev: &ClickEvent) {
+        if let Some(slot) = self
+            .widgets
+            .iter_mut()
+            .find(|slot| slot.block.name == ev.name && slot.block.instance == ev.instance)
+        {
+            // clicks on widgets that are busy get lost
+            if let Some(ref mut widget) = slot.widget {
+                if let Err(e) = widget.on_click(ev) {
+                    self.controller
+                        .do_send(WidgetError(slot.block.name.clone(), e));
+                }
+            }
+            // show the effect of the click right away
+            slot.due = Instant::now();
+        }
     }
 
     pub fn desktop_notifications_enabled(&self) -> bool {
@@ -54,14 +456,149 @@ impl Statusbar {
     }
 
     pub fn secure_default(controller: Addr<Bar>, general_cfg: GeneralCfg) -> Self {
-        use crate::widget::{datetime, net};
-        Self {
-            widgets: vec![
-                Box::new(net::Widget::new(net::Cfg::default()).unwrap()),
-                Box::new(datetime::Widget::new(datetime::Cfg::default())),
-            ],
-            general_cfg,
-            controller,
+        use crate::widget::{datetime, net, WidgetKind};
+        let widgets = vec![
+            WidgetKind::Net(net::Cfg::default()),
+            WidgetKind::Datetime(datetime::Cfg::default()),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, kind)| Slot::new(i, kind.into(), &general_cfg).unwrap())
+        .collect();
+        Self::with_slots(widgets, general_cfg, controller)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix::dev::channel;
+
+    use super::*;
+    use crate::widget::{datetime, WidgetKind};
+
+    fn recorded(c: Option<Color>, s: &str) -> String {
+        let mut rec = Recording::default();
+        match c {
+            Some(c) => rec.write_colored(c, format_args!("{}", s)),
+            None => rec.write(format_args!("{}", s)),
+        }
+        format!("{:?}", rec)
+    }
+
+    struct Hung(Receiver<()>);
+
+    impl Widget for Hung {
+        fn run(&mut self, _: &mut dyn Output) -> Result<(), failure::Error> {
+            let _ = self.0.recv();
+            Ok(())
         }
     }
+
+    #[test]
+    fn hung_widget_isnt_due() {
+        let mut cfg = WidgetCfg::from(WidgetKind::Datetime(datetime::Cfg::default()));
+        cfg.interval = Some(1000);
+        cfg.timeout = Some(10);
+        let mut slot = Slot::new(0, cfg, &GeneralCfg::default()).unwrap();
+        let (release, rx) = mpsc::channel();
+        slot.widget = Some(Box::new(Hung(rx)));
+
+        let (tx, done) = mpsc::channel();
+        let deadline = slot.spawn(0, &tx).unwrap();
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        assert!(done.recv_timeout(timeout).is_err());
+
+        // the interval is over but the widget still runs
+        slot.due = Instant::now();
+        assert!(slot.spawn(0, &tx).is_none());
+        assert!(slot.due > Instant::now());
+
+        release.send(()).unwrap();
+        assert_eq!(done.recv().unwrap().index, 0);
+    }
+
+    struct Panicking;
+
+    impl Widget for Panicking {
+        fn run(&mut self, _: &mut dyn Output) -> Result<(), failure::Error> {
+            panic!("broken widget")
+        }
+    }
+
+    #[test]
+    fn panicking_widget_fails() {
+        let cfg = WidgetCfg::from(WidgetKind::Datetime(datetime::Cfg::default()));
+        let mut slot = Slot::new(0, cfg, &GeneralCfg::default()).unwrap();
+        slot.widget = Some(Box::new(Panicking));
+
+        let (tx, done) = mpsc::channel();
+        slot.spawn(0, &tx).unwrap();
+        let done = done.recv().unwrap();
+        let e = done.res.unwrap_err();
+        assert_eq!(e.to_string(), "Panicked: broken widget");
+        assert!(slot.finish(done.written, Err(e)).is_some());
+        assert_eq!(
+            format!("{:?}", slot.last),
+            recorded(Some(Color::Bad), "datetime: ERR")
+        );
+    }
+
+    #[test]
+    fn failing_widget_falls_back() {
+        let mut cfg = WidgetCfg::from(WidgetKind::Datetime(datetime::Cfg::default()));
+        cfg.keep_last = Some(1);
+        cfg.format_error = Some("time: {error}".to_owned());
+        let mut slot = Slot::new(0, cfg, &GeneralCfg::default()).unwrap();
+
+        let mut written = Recording::default();
+        written.write(format_args!("12:00"));
+        assert!(slot.finish(written, Ok(())).is_none());
+
+        // partial output is dropped, the last good one is kept
+        let mut written = Recording::default();
+        written.write(format_args!("12:"));
+        assert!(slot.finish(written, Err(format_err!("no clock"))).is_some());
+        assert_eq!(format!("{:?}", slot.last), recorded(None, "12:00"));
+
+        // the same error isn't logged twice
+        let res = slot.finish(Recording::default(), Err(format_err!("no clock")));
+        assert!(res.is_none());
+        assert_eq!(
+            format!("{:?}", slot.last),
+            recorded(Some(Color::Bad), "time: no clock")
+        );
+    }
+
+    struct Titled(FormatString, FormatMap);
+
+    impl Widget for Titled {
+        fn run(&mut self, sink: &mut dyn Output) -> Result<(), failure::Error> {
+            sink.write_format(self.0.fmt(&self.1)?);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn pango_blocks_escape_values() {
+        let mut cfg = WidgetCfg::from(WidgetKind::Datetime(datetime::Cfg::default()));
+        cfg.block = Some(Block {
+            markup: Some(Markup::Pango),
+            ..Block::default()
+        });
+        let general = GeneralCfg::default();
+        let mut slot = Slot::new(0, cfg, &general).unwrap();
+        let mut map = FormatMap::new();
+        map.insert("title", "Rock & Roll".to_owned());
+        let format = FormatString::parse("<b>{title}</b>").unwrap();
+        slot.widget = Some(Box::new(Titled(format, map)));
+        // nothing reads what the statusbar sends to the bar
+        let (tx, _rx) = channel::channel(16);
+        let mut bar = Statusbar::with_slots(vec![slot], general, Addr::new(tx));
+
+        let mut out = Recording::default();
+        bar.update(&mut out);
+        let mut expected = Recording::default();
+        expected.write_markup(format_args!("<b>Rock &amp; Roll</b>"));
+        assert_eq!(format!("{:?}", out), format!("{:?}", expected));
+    }
 }
@@ -1,19 +1,22 @@
-use std::{fmt::Write, time::Duration};
+use std::{fmt::Write, fs, path::PathBuf, sync::mpsc::Sender, time::Duration};
 
+use super::{
+    control::{socket_path, Command, ControlSocket},
+    notify::{Desktop, Notifications, Notifier, Silent},
+    status::Statusbar,
+};
+use crate::{
+    config::{Config, Format, GeneralCfg, OutputCfg, Source},
+    output::{output_from_cfgs, ClickEvent, Output, SinkCfg},
+};
 use actix::prelude::{
     Actor, Addr, AsyncContext, Context, Handler, Message, SpawnHandle, SyncArbiter, SyncContext,
     System,
 };
 use log::*;
-use notify_rust::Notification;
-
-use super::status::Statusbar;
-use crate::{
-    config::{Config, Format},
-    output::{output_from_format, Output},
-};
+use nix::sys::signal::{SigSet, Signal};
 
-fn format_error(err: &failure::Error) -> String {
+pub(super) fn format_error(err: &failure::Error) -> String {
     let mut ret = format!("{}\n", err);
     for cause in err.iter_causes() {
         writeln!(ret, "{}", cause).unwrap();
@@ -24,10 +27,31 @@ fn format_error(err: &failure::Error) -> String {
     ret
 }
 
+fn notification_cooldown(general: &GeneralCfg) -> Duration {
+    Duration::from_millis(u64::from(general.notification_cooldown.unwrap_or(60_000)))
+}
+
+fn log_error(err: &failure::Error) {
+    error!("{}", err);
+    for cause in err.iter_causes() {
+        error!("Caused by: {}", cause)
+    }
+}
+
 pub struct Bar {
     bar: Statusbar,
     output: Box<dyn Output>,
+    config_path: PathBuf,
+    // format given on the command line
+    format: Option<Format>,
+    outputs: Vec<OutputCfg>,
+    redraw_interval: Option<u32>,
+    notifications: Notifications,
     last_future_tick: SpawnHandle,
+    // i3bar is hidden and sent its stop signal
+    paused: bool,
+    // the control socket this bar listens on, removed when exiting
+    socket: Option<PathBuf>,
 }
 
 impl Actor for Bar {
@@ -36,41 +60,173 @@ impl Actor for Bar {
 
 impl Bar {
     fn schedule_tick(&mut self, ctx: &mut Context<Self>) {
-        self.last_future_tick = ctx.notify_later(Update, tick_duration(self.bar.update_interval()));
+        if !self.paused {
+            self.last_future_tick = ctx.notify_later(Update, self.bar.next_update());
+        }
+    }
+
+    /// Nothing runs while paused
+    fn update(&mut self) {
+        if !self.paused {
+            self.bar.update(&mut *self.output);
+        }
+    }
+
+    fn apply_config(&mut self, cfg: Config, ctx: &mut Context<Self>) -> Result<(), failure::Error> {
+        let sep = cfg.general.separator.clone();
+        let colors = cfg.colors.clone();
+        let outputs = cfg.outputs(self.format);
+        let redraw_interval = cfg.general.redraw_interval;
+        let cooldown = notification_cooldown(&cfg.general);
+        // keeps the old widgets running if the new ones are broken
+        let mut bar = Statusbar::new(cfg, ctx.address())?;
+        bar.spawn_wakeups();
+        ctx.cancel_future(self.last_future_tick);
+        self.bar = bar;
+        self.notifications.set_cooldown(cooldown);
+        if outputs == self.outputs && redraw_interval == self.redraw_interval {
+            self.output.set_sep(sep);
+            self.output.set_colors(&colors);
+        } else {
+            // new outputs or sinks haven't seen anything yet
+            self.output.close();
+            self.output = output_from_cfgs(sep, colors, &outputs, redraw_interval);
+            self.output.init();
+            self.outputs = outputs;
+            self.redraw_interval = redraw_interval;
+        }
+        info!("Updated config");
+        self.update();
+        self.schedule_tick(ctx);
+        Ok(())
     }
-}
 
-fn tick_duration(interval: u32) -> Duration {
-    Duration::from_millis(u64::from(interval))
+    fn refresh(&mut self) {
+        self.bar.refresh();
+        self.update();
+    }
+
+    fn reload(&mut self, ctx: &mut Context<Self>) -> Result<String, failure::Error> {
+        self.apply_config(Config::load(&self.config_path)?, ctx)?;
+        Ok(String::new())
+    }
 }
 
 impl Handler<Update> for Bar {
     type Result = ();
     fn handle(&mut self, _msg: Update, mut ctx: &mut Context<Self>) {
+        self.update();
         self.schedule_tick(&mut ctx);
-        self.bar.update(&mut *self.output);
     }
 }
 
 impl Handler<NewConfig> for Bar {
     type Result = ();
-    fn handle(&mut self, NewConfig(cfg): NewConfig, mut ctx: &mut Context<Self>) {
-        ctx.cancel_future(self.last_future_tick);
-        let sep = cfg.general.separator.clone();
-        let colors = cfg.colors.clone();
-        match Statusbar::new(cfg, ctx.address()) {
-            Ok(bar) => {
-                self.bar = bar;
-                self.output.set_sep(sep);
-                self.output.set_colors(&colors);
-                info!("Updated config");
-                self.bar.update(&mut *self.output);
+    fn handle(&mut self, NewConfig(cfg): NewConfig, ctx: &mut Context<Self>) {
+        if let Err(e) = self.apply_config(cfg, ctx) {
+            ctx.address().do_send(ErrorLog(e));
+        }
+    }
+}
+
+impl Handler<Control> for Bar {
+    type Result = ();
+
+    fn handle(&mut self, Control(cmd, reply): Control, ctx: &mut Context<Self>) {
+        let res = match cmd {
+            Command::Refresh => {
+                self.refresh();
+                Ok(String::new())
             }
-            Err(e) => {
-                ctx.address().do_send(ErrorLog(e));
+            Command::Reload => self.reload(ctx),
+            Command::Hide(ref widget) | Command::Show(ref widget) => {
+                let res = self.bar.set_hidden(widget, matches!(cmd, Command::Hide(_)));
+                self.update();
+                res.map(|_| String::new())
+            }
+            Command::Dump => Ok(self.bar.dump()),
+            Command::SetFormat(format) => {
+                self.format = Some(format);
+                self.reload(ctx)
+            }
+        };
+        // the client might be gone already
+        let _ = reply.send(res);
+    }
+}
+
+impl Handler<Refresh> for Bar {
+    type Result = ();
+    fn handle(&mut self, _msg: Refresh, _ctx: &mut Context<Self>) {
+        self.refresh();
+    }
+}
+
+impl Handler<Reload> for Bar {
+    type Result = ();
+    fn handle(&mut self, _msg: Reload, ctx: &mut Context<Self>) {
+        if let Err(e) = self.reload(ctx) {
+            ctx.address().do_send(ErrorLog(e));
+        }
+    }
+}
+
+impl Handler<Pause> for Bar {
+    type Result = ();
+    fn handle(&mut self, _msg: Pause, ctx: &mut Context<Self>) {
+        debug!("Paused");
+        self.paused = true;
+        ctx.cancel_future(self.last_future_tick);
+    }
+}
+
+impl Handler<Resume> for Bar {
+    type Result = ();
+    fn handle(&mut self, _msg: Resume, ctx: &mut Context<Self>) {
+        // SIGCONT also arrives when nothing was paused
+        if self.paused {
+            debug!("Resumed");
+            self.paused = false;
+            self.refresh();
+            self.schedule_tick(ctx);
+        }
+    }
+}
+
+impl Handler<Quit> for Bar {
+    type Result = ();
+    fn handle(&mut self, Quit(code): Quit, _ctx: &mut Context<Self>) {
+        info!("Exiting");
+        self.output.close();
+        if let Some(ref path) = self.socket {
+            if let Err(e) = fs::remove_file(path) {
+                warn!("Can't remove {}: {}", path.display(), e);
             }
         }
-        self.schedule_tick(&mut ctx);
+        System::current().stop_with_code(code);
+    }
+}
+
+impl Handler<Listening> for Bar {
+    type Result = ();
+    fn handle(&mut self, Listening(path): Listening, _ctx: &mut Context<Self>) {
+        self.socket = Some(path);
+    }
+}
+
+impl Handler<Click> for Bar {
+    type Result = ();
+    fn handle(&mut self, Click(ev): Click, _ctx: &mut Context<Self>) {
+        self.bar.click(&ev);
+        self.update();
+    }
+}
+
+impl Handler<Wake> for Bar {
+    type Result = ();
+    fn handle(&mut self, Wake(index): Wake, _ctx: &mut Context<Self>) {
+        self.bar.wake(index);
+        self.update();
     }
 }
 
@@ -78,20 +234,43 @@ impl Handler<ErrorLog> for Bar {
     type Result = ();
 
     fn handle(&mut self, ErrorLog(msg): ErrorLog, _ctx: &mut Context<Self>) {
-        error!("{}", msg);
-        for cause in msg.iter_causes() {
-            error!("Caused by: {}", cause)
+        log_error(&msg);
+        if self.bar.desktop_notifications_enabled() {
+            self.notifications.error(None, &msg);
+        }
+    }
+}
+
+impl Handler<WidgetError> for Bar {
+    type Result = ();
+
+    fn handle(&mut self, WidgetError(widget, msg): WidgetError, _ctx: &mut Context<Self>) {
+        error!("Widget {} failed", widget);
+        log_error(&msg);
+        if self.bar.desktop_notifications_enabled() {
+            self.notifications.error(Some(&widget), &msg);
         }
+    }
+}
 
+impl Handler<Alerted> for Bar {
+    type Result = ();
+
+    fn handle(&mut self, Alerted(widget, message): Alerted, _ctx: &mut Context<Self>) {
+        warn!("Alert from {}: {}", widget, message);
         if self.bar.desktop_notifications_enabled() {
-            if let Err(e) = Notification::new()
-                .summary("statusbar-rs error")
-                // FIXME:
-                .body(&format_error(&msg))
-                .show()
-            {
-                warn!("{}", e);
-            }
+            self.notifications.alert(&widget, &message);
+        }
+    }
+}
+
+impl Handler<Recovered> for Bar {
+    type Result = ();
+
+    fn handle(&mut self, Recovered(widget): Recovered, _ctx: &mut Context<Self>) {
+        info!("Widget {} works again", widget);
+        if self.bar.desktop_notifications_enabled() {
+            self.notifications.resolved(&widget);
         }
     }
 }
@@ -102,85 +281,305 @@ struct Update;
 #[derive(Message)]
 struct NewConfig(Config);
 
+#[derive(Message)]
+struct Click(ClickEvent);
+
+#[derive(Message)]
+struct Refresh;
+
+#[derive(Message)]
+struct Reload;
+
+#[derive(Message)]
+struct Pause;
+
+#[derive(Message)]
+struct Resume;
+
+/// Stops the bar with that exit code
+#[derive(Message)]
+struct Quit(i32);
+
+/// The control socket is bound to that path
+#[derive(Message)]
+pub struct Listening(pub PathBuf);
+
+/// A command from the control socket and where its reply goes
+#[derive(Message)]
+pub struct Control(pub Command, pub Sender<Result<String, failure::Error>>);
+
+/// Sent by the wakeup thread of the widget at that index
+#[derive(Message)]
+pub struct Wake(pub usize);
+
 #[derive(Message)]
 pub struct ErrorLog(pub failure::Error);
 
+/// An error of the widget with that name
+#[derive(Message)]
+pub struct WidgetError(pub String, pub failure::Error);
+
+/// A value of the widget with that name crossed the limit of one of its alerts
+#[derive(Message)]
+pub struct Alerted(pub String, pub String);
+
+/// Sent after a widget that failed or timed out ran successfully
+#[derive(Message)]
+pub struct Recovered(pub String);
+
 struct ConfigWatcher {
     tx: Addr<Bar>,
+    path: PathBuf,
+}
+
+/// Blocks until one of `paths` gets written or replaced. Watches their directories instead of
+/// the files so files that get replaced by editors or don't exist yet are covered too.
+fn wait_for_change(paths: &[PathBuf]) -> Result<(), failure::Error> {
+    use inotify::{EventMask, Inotify, WatchMask};
+    use std::thread;
+
+    let mut inotify = Inotify::init()?;
+    let mut watched = Vec::new();
+    loop {
+        for path in paths {
+            let (dir, name) = match (path.parent(), path.file_name()) {
+                (Some(dir), Some(name)) => (dir, name),
+                _ => continue,
+            };
+            let mask = WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::CREATE;
+            match inotify.add_watch(dir, mask) {
+                Ok(wd) => watched.push((wd, name.to_owned())),
+                // e.g. `hosts/`, wait for it to get created
+                Err(_) => {
+                    if let (Some(parent), Some(dir_name)) = (dir.parent(), dir.file_name()) {
+                        if let Ok(wd) = inotify.add_watch(parent, mask) {
+                            watched.push((wd, dir_name.to_owned()));
+                        }
+                    }
+                }
+            }
+        }
+        if !watched.is_empty() {
+            break;
+        }
+        thread::sleep(Duration::new(10, 0));
+    }
+
+    let mut buf = [0u8; 4096];
+    loop {
+        for event in inotify.read_events_blocking(&mut buf)? {
+            // files get written after they're created
+            if event.mask.contains(EventMask::CREATE) && !event.mask.contains(EventMask::ISDIR) {
+                continue;
+            }
+            if let Some(name) = event.name {
+                if watched
+                    .iter()
+                    .any(|(wd, watched)| *wd == event.wd && watched == name)
+                {
+                    return Ok(());
+                }
+            }
+        }
+    }
 }
 
 impl Actor for ConfigWatcher {
     type Context = SyncContext<Self>;
     fn started(&mut self, _ctx: &mut Self::Context) {
-        use crate::config;
-        use inotify::{Inotify, WatchMask};
-        use std::thread;
-
-        let mut inotify = Inotify::init().unwrap();
+        // the includes can change with every reload
+        let mut files = Config::load_with_files(&self.path)
+            .map(|loaded| loaded.files)
+            .unwrap_or_default();
+        loop {
+            let res = wait_for_change(&Config::watched_paths(&self.path, &files))
+                .and_then(|()| Config::load_with_files(&self.path).map_err(Into::into));
+            match res {
+                Ok(loaded) => {
+                    loaded.warn_unknown_keys();
+                    files = loaded.files;
+                    self.tx.do_send(NewConfig(loaded.cfg));
+                }
+                Err(e) => self.tx.do_send(ErrorLog(e)),
+            }
+        }
+    }
+}
 
-        let watch_config = |ino: &mut Inotify| {
-            ino.add_watch(
-                config::CONFIG_PATH.as_path(),
-                WatchMask::CLOSE_WRITE | WatchMask::DELETE_SELF,
-            )
-        };
+/// Sent by i3bar when it gets hidden, it would use SIGSTOP if we didn't tell it otherwise
+pub const STOP_SIGNAL: Signal = Signal::SIGUSR2;
+pub const CONT_SIGNAL: Signal = Signal::SIGCONT;
 
-        let _ = watch_config(&mut inotify);
+/// Handled by `SignalReader`, blocked in every other thread
+fn handled_signals() -> SigSet {
+    let mut signals = SigSet::empty();
+    for &signal in &[
+        Signal::SIGUSR1,
+        Signal::SIGHUP,
+        Signal::SIGTERM,
+        Signal::SIGINT,
+        STOP_SIGNAL,
+        CONT_SIGNAL,
+    ] {
+        signals.add(signal);
+    }
+    signals
+}
 
-        // Ignore this unused, bug in nll
-        let mut buf = [0u8; 4096];
+struct SignalReader {
+    tx: Addr<Bar>,
+    signals: SigSet,
+}
 
-        let tx = self.tx.clone();
-        let mut on_event = move || -> Result<(), failure::Error> {
-            let events = inotify.read_events_blocking(&mut buf)?;
-            for event in events {
-                if event.mask.contains(inotify::EventMask::DELETE_SELF)
-                    && watch_config(&mut inotify).is_err()
-                {
-                    while watch_config(&mut inotify).is_err() {
-                        thread::sleep(Duration::new(10, 0));
-                    }
+impl Actor for SignalReader {
+    type Context = SyncContext<Self>;
+    fn started(&mut self, _ctx: &mut Self::Context) {
+        loop {
+            match self.signals.wait() {
+                Ok(Signal::SIGUSR1) => self.tx.do_send(Refresh),
+                Ok(Signal::SIGHUP) => self.tx.do_send(Reload),
+                Ok(STOP_SIGNAL) => self.tx.do_send(Pause),
+                Ok(CONT_SIGNAL) => self.tx.do_send(Resume),
+                Ok(_) => {
+                    self.tx.do_send(Quit(0));
+                    break;
+                }
+                Err(e) => {
+                    self.tx.do_send(ErrorLog(e.into()));
+                    break;
                 }
             }
+        }
+    }
+}
 
-            let cfg = Config::load()?;
-
-            tx.do_send(NewConfig(cfg));
+struct ClickReader {
+    tx: Addr<Bar>,
+}
 
-            Ok(())
-        };
+impl Actor for ClickReader {
+    type Context = SyncContext<Self>;
+    fn started(&mut self, _ctx: &mut Self::Context) {
+        use std::io::{self, BufRead};
 
+        let stdin = io::stdin();
+        let mut stdin = stdin.lock();
+        let mut line = String::new();
         loop {
-            if let Err(e) = on_event() {
-                self.tx.do_send(ErrorLog(e));
+            line.clear();
+            match stdin.read_line(&mut line) {
+                // bar closed stdin, there won't be any more clicks
+                Ok(0) => break,
+                Ok(_) => match ClickEvent::from_line(&line) {
+                    Ok(Some(ev)) => self.tx.do_send(Click(ev)),
+                    Ok(None) => {}
+                    Err(e) => self.tx.do_send(ErrorLog(e.into())),
+                },
+                Err(e) => {
+                    self.tx.do_send(ErrorLog(e.into()));
+                    break;
+                }
             }
         }
     }
 }
 
-pub fn run(cfg: Config, output_format: Option<Format>) {
+/// Runs until a signal stops it or, with `once`, renders one frame, returns the exit code
+pub fn run(cfg: Config, source: Source, output_format: Option<Format>, once: bool) -> i32 {
+    // threads inherit the mask so this has to happen before any of them get spawned
+    let signals = handled_signals();
+    let signals_blocked = !once
+        && match signals.thread_block() {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("Can't block signals, exiting on them won't be clean: {}", e);
+                false
+            }
+        };
+
     let sys = System::new("bar");
 
-    let format = output_format.unwrap_or(cfg.general.default_output_format);
-    let mut output = output_from_format(cfg.general.separator.clone(), cfg.colors.clone(), format);
+    let outputs = cfg.outputs(output_format);
+    let redraw_interval = cfg.general.redraw_interval;
+    let notifier: Box<dyn Notifier> = if once {
+        Box::new(Silent)
+    } else {
+        Box::new(Desktop)
+    };
+    let notifications = Notifications::new(notifier, notification_cooldown(&cfg.general));
+    let mut output = output_from_cfgs(
+        cfg.general.separator.clone(),
+        cfg.colors.clone(),
+        &outputs,
+        redraw_interval,
+    );
     output.init();
 
-    let bar = Bar::create(move |ctx: &mut Context<Bar>| {
-        let last = ctx.notify_later(Update, tick_duration(cfg.general.update_interval));
+    // only i3bar sends clicks and it only reads our stdout
+    let clickable = outputs
+        .iter()
+        .any(|out| out.format == Format::I3 && out.sink == SinkCfg::Stdout);
 
+    let config_path = source.path.clone();
+    let bar = Bar::create(move |ctx: &mut Context<Bar>| {
+        let mut failed = false;
         let mut bar = Statusbar::new(cfg, ctx.address()).unwrap_or_else(|e| {
+            failed = true;
             ctx.address().do_send(ErrorLog(e));
             let general = crate::config::GeneralCfg::default();
             Statusbar::secure_default(ctx.address(), general)
         });
+        if !once {
+            bar.spawn_wakeups();
+        }
         bar.update(&mut *output);
+        if once {
+            // comes after the errors of the run so they still get logged
+            let code = if failed || bar.failed() { 1 } else { 0 };
+            ctx.address().do_send(Quit(code));
+        }
+        let last = ctx.notify_later(Update, bar.next_update());
         Bar {
             bar,
             last_future_tick: last,
             output,
+            config_path,
+            format: output_format,
+            outputs,
+            redraw_interval,
+            notifications,
+            paused: false,
+            socket: None,
         }
     });
 
-    SyncArbiter::start(1, move || ConfigWatcher { tx: bar.clone() });
-    sys.run();
+    if once {
+        return sys.run();
+    }
+    if clickable {
+        let tx = bar.clone();
+        SyncArbiter::start(1, move || ClickReader { tx: tx.clone() });
+    }
+    match socket_path(source.name.as_deref()) {
+        Some(path) => {
+            let tx = bar.clone();
+            SyncArbiter::start(1, move || ControlSocket {
+                path: path.clone(),
+                tx: tx.clone(),
+            });
+        }
+        None => warn!("XDG_RUNTIME_DIR isn't set, not listening for commands"),
+    }
+    if signals_blocked {
+        let tx = bar.clone();
+        SyncArbiter::start(1, move || SignalReader {
+            tx: tx.clone(),
+            signals,
+        });
+    }
+    SyncArbiter::start(1, move || ConfigWatcher {
+        tx: bar.clone(),
+        path: source.path.clone(),
+    });
+    sys.run()
 }
@@ -7,14 +7,103 @@ pub mod net;
 pub mod temp;
 pub mod volume;
 
+use std::{os::unix::io::RawFd, time::Duration};
+
+use formatter::FormatMap;
+use nix::{
+    errno::Errno,
+    poll::{poll, EventFlags, PollFd},
+    unistd::{close, pipe2},
+};
 use serde_derive::{Deserialize, Serialize};
 
-use crate::output::Output;
+use crate::{
+    output::{Block, ClickEvent, Output},
+    statusbar::AlertCfg,
+};
 
-pub trait Widget {
+/// Widgets run on a thread pool
+pub trait Widget: Send {
     fn run(&mut self, _: &mut dyn Output) -> Result<(), failure::Error>;
+
+    /// Called when one of the blocks this widget wrote got clicked
+    fn on_click(&mut self, _: &ClickEvent) -> Result<(), failure::Error> {
+        Ok(())
+    }
+
+    /// Source of changes that should show up before the next interval is over
+    fn wakeup(&self) -> Option<Box<dyn Wakeup>> {
+        None
+    }
+
+    /// Values of the last run, alerts are checked against them
+    fn values(&self) -> Option<&FormatMap> {
+        None
+    }
 }
 
+/// Gets its own thread
+pub trait Wakeup: Send {
+    /// Blocks and calls `wake` every time the widget has something new to show, returns once
+    /// `wake` returns false or `cancel` fires
+    fn run(
+        self: Box<Self>,
+        cancel: &Cancel,
+        wake: &mut dyn FnMut() -> bool,
+    ) -> Result<(), failure::Error>;
+}
+
+/// Read end of a pipe that hangs up once its `Canceller` is dropped, wakeups block on it together
+/// with their own fd so they don't outlive their widget
+pub struct Cancel(RawFd);
+
+/// Write end of the pipe
+pub struct Canceller(RawFd);
+
+impl Drop for Cancel {
+    fn drop(&mut self) {
+        let _ = close(self.0);
+    }
+}
+
+impl Drop for Canceller {
+    fn drop(&mut self) {
+        let _ = close(self.0);
+    }
+}
+
+impl Cancel {
+    pub fn new() -> Result<(Canceller, Cancel), failure::Error> {
+        let (read, write) = pipe2(nix::fcntl::OFlag::O_CLOEXEC)?;
+        Ok((Canceller(write), Cancel(read)))
+    }
+
+    /// Blocks until `fd` is readable, false if cancelled before
+    pub fn wait(&self, fd: RawFd) -> Result<bool, failure::Error> {
+        self.poll(Some(fd), -1)
+    }
+
+    /// False if cancelled before `timeout` is over
+    pub fn sleep(&self, timeout: Duration) -> Result<bool, failure::Error> {
+        self.poll(None, timeout.as_millis() as i32)
+    }
+
+    fn poll(&self, fd: Option<RawFd>, timeout: i32) -> Result<bool, failure::Error> {
+        let mut fds = vec![PollFd::new(self.0, EventFlags::POLLIN)];
+        fds.extend(fd.map(|fd| PollFd::new(fd, EventFlags::POLLIN)));
+        loop {
+            match poll(&mut fds, timeout) {
+                Err(nix::Error::Sys(Errno::EINTR)) => continue,
+                res => res?,
+            };
+            return Ok(fds[0].revents().is_some_and(|events| events.is_empty()));
+        }
+    }
+}
+
+/// Format strings of a widget by field name with the keys they can use
+pub type Formats<'a> = Vec<(&'static str, &'a str, &'static [&'static str])>;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "snake_case")]
 #[serde(tag = "type")]
@@ -29,11 +118,80 @@ pub enum WidgetKind {
     CpuUsage(cpu_usage::Cfg),
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WidgetCfg {
+    #[serde(flatten)]
+    pub kind: WidgetKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block: Option<Block>,
+    /// Milliseconds between runs of this widget, defaults to `general.update_interval`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval: Option<u32>,
+    /// Milliseconds a run may take before the last output is shown as stale, defaults to
+    /// `general.update_interval`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u32>,
+    /// Failed runs that still show the last successful output, defaults to 3
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_last: Option<u32>,
+    /// Shown once the widget kept failing, `{error}` is the last error, defaults to
+    /// `"<type>: ERR"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format_error: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alerts: Vec<AlertCfg>,
+}
+
+impl From<WidgetKind> for WidgetCfg {
+    fn from(kind: WidgetKind) -> Self {
+        Self {
+            kind,
+            block: None,
+            interval: None,
+            timeout: None,
+            keep_last: None,
+            format_error: None,
+            alerts: Vec::new(),
+        }
+    }
+}
+
+impl WidgetKind {
+    pub fn name(&self) -> &'static str {
+        use self::WidgetKind::*;
+        match self {
+            Battery(_) => "battery",
+            Datetime(_) => "datetime",
+            Mpd(_) => "mpd",
+            Volume(_) => "volume",
+            Net(_) => "net",
+            Memory(_) => "memory",
+            Temp(_) => "temp",
+            CpuUsage(_) => "cpu_usage",
+        }
+    }
+
+    pub fn formats(&self) -> Formats<'_> {
+        use self::WidgetKind::*;
+        match self {
+            Battery(cfg) => cfg.formats(),
+            // uses strftime, see `datetime::Cfg::check_format`
+            Datetime(_) => Vec::new(),
+            Mpd(cfg) => cfg.formats(),
+            Volume(cfg) => cfg.formats(),
+            Net(cfg) => cfg.formats(),
+            Memory(cfg) => cfg.formats(),
+            Temp(cfg) => cfg.formats(),
+            CpuUsage(cfg) => cfg.formats(),
+        }
+    }
+}
+
 pub fn widget_from_kind(kind: WidgetKind) -> Result<Box<dyn Widget>, failure::Error> {
     use self::WidgetKind::*;
     Ok(match kind {
         Battery(cfg) => Box::new(battery::Widget::new(cfg)?),
-        Datetime(cfg) => Box::new(datetime::Widget::new(cfg)),
+        Datetime(cfg) => Box::new(datetime::Widget::new(cfg)?),
         Mpd(cfg) => Box::new(mpd::Widget::new(cfg)?),
         Volume(cfg) => Box::new(volume::Widget::new(cfg)?),
         Net(cfg) => Box::new(net::Widget::new(cfg)?),
@@ -42,3 +200,17 @@ pub fn widget_from_kind(kind: WidgetKind) -> Result<Box<dyn Widget>, failure::Er
         CpuUsage(cfg) => Box::new(cpu_usage::Widget::new(cfg)?),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dropping_canceller_cancels() {
+        let (canceller, cancel) = Cancel::new().unwrap();
+        assert!(cancel.sleep(Duration::from_millis(1)).unwrap());
+        drop(canceller);
+        assert!(!cancel.sleep(Duration::from_secs(60)).unwrap());
+        assert!(!cancel.wait(cancel.0).unwrap());
+    }
+}
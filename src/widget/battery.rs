@@ -1,11 +1,18 @@
 use std::{
     fs::File,
     io::{self, BufReader},
+    os::unix::io::RawFd,
     path::PathBuf,
 };
 
 use failure::{self, format_err};
 use formatter::{FormatMap, FormatString};
+use nix::{
+    errno::Errno,
+    libc,
+    sys::socket::{bind, recv, MsgFlags, SockAddr},
+    unistd::close,
+};
 use serde_derive::{Deserialize, Serialize};
 
 use crate::{
@@ -13,6 +20,8 @@ use crate::{
     widget,
 };
 
+const KEYS: &[&str] = &["sym", "charge", "status"];
+
 pub struct Widget {
     fmt_map: FormatMap,
     bat_path: PathBuf,
@@ -41,7 +50,7 @@ impl Widget {
             sym_charging: cfg.sym_charging,
             sym_discharging: cfg.sym_discharging,
             mediocre_treshold: cfg.mediocre_treshold,
-            format: FormatString::parse_with_allowed_keys(&cfg.format, &["sym", "charge"])?,
+            format: FormatString::parse_with_allowed_keys(&cfg.format, KEYS)?,
             sym_unknown: cfg.sym_unknown,
         })
     }
@@ -54,6 +63,17 @@ pub enum Status {
     Discharging,
 }
 
+impl Status {
+    /// Spelled like `POWER_SUPPLY_STATUS` in the uevent
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Status::Unknown => "Unknown",
+            Status::Charging => "Charging",
+            Status::Discharging => "Discharging",
+        }
+    }
+}
+
 impl ::std::str::FromStr for Status {
     type Err = ();
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -155,8 +175,11 @@ impl widget::Widget for Widget {
             self.fmt_map
                 .update_string_with("sym", |s| s.clone_from(sym));
             self.fmt_map.insert("charge", charge);
+            self.fmt_map.update_string_with("status", |s| {
+                s.push_str(uevent.power_supply_status.as_str())
+            });
 
-            sink.write_colored(color, format_args!("{}", self.format.fmt(&self.fmt_map)?));
+            sink.write_format_colored(color, self.format.fmt(&self.fmt_map)?);
 
             Ok(())
         } else {
@@ -166,9 +189,72 @@ impl widget::Widget for Widget {
             ))
         }
     }
+
+    fn wakeup(&self) -> Option<Box<dyn widget::Wakeup>> {
+        Some(Box::new(Wakeup))
+    }
+
+    fn values(&self) -> Option<&FormatMap> {
+        Some(&self.fmt_map)
+    }
+}
+
+// from linux/netlink.h
+const NETLINK_KOBJECT_UEVENT: libc::c_int = 15;
+// uevents straight from the kernel, not the ones udev sends again
+const KERNEL_UEVENTS: u32 = 1;
+
+struct UeventSock(RawFd);
+
+impl Drop for UeventSock {
+    fn drop(&mut self) {
+        let _ = close(self.0);
+    }
+}
+
+/// Wakes up on uevents of power supplies, sysfs attributes don't raise inotify events
+struct Wakeup;
+
+impl widget::Wakeup for Wakeup {
+    fn run(
+        self: Box<Self>,
+        cancel: &widget::Cancel,
+        wake: &mut dyn FnMut() -> bool,
+    ) -> Result<(), failure::Error> {
+        let sock = UeventSock(Errno::result(unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+                NETLINK_KOBJECT_UEVENT,
+            )
+        })?);
+        bind(sock.0, &SockAddr::new_netlink(0, KERNEL_UEVENTS))?;
+        let mut buf = [0u8; 4096];
+        while cancel.wait(sock.0)? {
+            let changed = match recv(sock.0, &mut buf, MsgFlags::empty()) {
+                // the adapter getting plugged in changes the status of the battery too
+                Ok(len) => is_power_supply(&buf[..len]),
+                // uevents got dropped because the buffer was full, some might have been ours
+                Err(nix::Error::Sys(Errno::ENOBUFS)) => true,
+                Err(e) => return Err(e.into()),
+            };
+            if changed && !wake() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+// uevents are an `action@devpath` header followed by `KEY=value` pairs, all NUL terminated
+fn is_power_supply(uevent: &[u8]) -> bool {
+    uevent
+        .split(|&b| b == 0)
+        .any(|field| field == b"SUBSYSTEM=power_supply")
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
 pub struct Cfg {
     pub bat_name: String,
     pub format: String,
@@ -179,6 +265,12 @@ pub struct Cfg {
     pub bad_treshold: f64,
 }
 
+impl Cfg {
+    pub fn formats(&self) -> widget::Formats<'_> {
+        vec![("format", &self.format, KEYS)]
+    }
+}
+
 impl Default for Cfg {
     fn default() -> Self {
         Self {
@@ -192,3 +284,18 @@ impl Default for Cfg {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn power_supply_uevents() {
+        let uevent = b"change@/devices/LNXSYSTM:00/PNP0C0A:00/power_supply/BAT0\0ACTION=change\0\
+                       SUBSYSTEM=power_supply\0POWER_SUPPLY_NAME=BAT0\0";
+        assert!(is_power_supply(uevent));
+        assert!(!is_power_supply(
+            b"add@/devices/virtual/net/tun0\0SUBSYSTEM=net\0"
+        ));
+    }
+}
@@ -1,36 +1,70 @@
 #[cfg(target_os = "linux")]
 mod linux;
 
+use std::{thread::sleep, time::Duration};
+
 #[cfg(target_os = "linux")]
-use self::linux::get_cpu_usage;
+use self::linux::Sample;
 use crate::output::Output;
 
 use formatter::{FormatMap, FormatString};
 use serde_derive::{Deserialize, Serialize};
 
+const KEYS: &[&str] = &["usage"];
+
 pub struct Widget {
     fmt_map: FormatMap,
     format: FormatString,
+    // usage is measured since the last run
+    last: Option<Sample>,
 }
 
 impl Widget {
     pub fn new(cfg: Cfg) -> Result<Self, failure::Error> {
         Ok(Self {
             fmt_map: FormatMap::new(),
-            format: FormatString::parse_with_allowed_keys(&cfg.format, &["usage"])?,
+            format: FormatString::parse_with_allowed_keys(&cfg.format, KEYS)?,
+            last: None,
         })
     }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct Cfg {
     format: String,
 }
 
+impl Cfg {
+    pub fn formats(&self) -> super::Formats<'_> {
+        vec![("format", &self.format, KEYS)]
+    }
+}
+
+impl Default for Cfg {
+    fn default() -> Self {
+        Self {
+            format: "cpu: {usage:.0}%".to_owned(),
+        }
+    }
+}
+
 impl super::Widget for Widget {
     fn run(&mut self, sink: &mut dyn Output) -> Result<(), failure::Error> {
-        self.fmt_map.insert("usage", get_cpu_usage());
-        sink.write(format_args!("{}", self.format.fmt(&self.fmt_map)?));
+        let prev = self.last.unwrap_or_else(|| {
+            // the first run has nothing to compare with
+            let first = Sample::read();
+            sleep(Duration::from_millis(50));
+            first
+        });
+        let sample = Sample::read();
+        self.last = Some(sample);
+        self.fmt_map.insert("usage", sample.usage_since(&prev));
+        sink.write_format(self.format.fmt(&self.fmt_map)?);
         Ok(())
     }
+
+    fn values(&self) -> Option<&FormatMap> {
+        Some(&self.fmt_map)
+    }
 }
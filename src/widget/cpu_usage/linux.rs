@@ -1,8 +1,15 @@
-use std::{fs::File, io::prelude::*, str, thread::sleep, time::Duration};
+use std::{fs::File, io::prelude::*, str};
 
-pub fn get_cpu_usage() -> f64 {
-    let mut buf = [0; 4096];
-    let mut get_usage = || -> (f64, f64) {
+/// Time all cpus spent since boot
+#[derive(Debug, Clone, Copy)]
+pub struct Sample {
+    busy: f64,
+    total: f64,
+}
+
+impl Sample {
+    pub fn read() -> Self {
+        let mut buf = [0; 4096];
         let mut fh = File::open("/proc/stat").expect("procfs not mounted");
         let nbytes = fh.read(&mut buf).unwrap();
 
@@ -13,17 +20,24 @@ pub fn get_cpu_usage() -> f64 {
             .map(|s| s.parse::<f64>().unwrap());
 
         // can't use take because take consumes the entire iterator
-        let normal = it.next().unwrap() + it.next().unwrap() + it.next().unwrap();
+        let busy = it.next().unwrap() + it.next().unwrap() + it.next().unwrap();
         let idle = it.next().unwrap();
-        (normal, idle + normal)
-    };
-
-    let first_measure = get_usage();
-    // 50ms wasted. too bad futures are currently unusable
-    sleep(Duration::from_millis(50));
-    let second_measure = get_usage();
+        Self {
+            busy,
+            total: idle + busy,
+        }
+    }
 
-    (second_measure.0 - first_measure.0) / (second_measure.1 - first_measure.1) * 100.
+    /// Usage in percent between `prev` and this sample
+    pub fn usage_since(&self, prev: &Sample) -> f64 {
+        let total = self.total - prev.total;
+        // /proc/stat only changes every few milliseconds
+        if total <= 0. {
+            0.
+        } else {
+            (self.busy - prev.busy) / total * 100.
+        }
+    }
 }
 
 #[cfg(test)]
@@ -31,7 +45,10 @@ mod tests {
     use super::*;
 
     #[test]
-    fn get_cpu_usage_seems_to_work() {
-        assert!(get_cpu_usage() >= 0.0);
+    fn cpu_usage_seems_to_work() {
+        let first = Sample::read();
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        let usage = Sample::read().usage_since(&first);
+        assert!((0.0..=100.0).contains(&usage));
     }
 }
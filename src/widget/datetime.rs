@@ -1,4 +1,8 @@
-use chrono::{Local, Utc};
+use chrono::{
+    format::{Item, StrftimeItems},
+    Local, Utc,
+};
+use failure::format_err;
 use serde_derive::{Deserialize, Serialize};
 
 use crate::{output::Output, widget};
@@ -8,8 +12,9 @@ pub struct Widget {
 }
 
 impl Widget {
-    pub fn new(cfg: Cfg) -> Self {
-        Self { cfg }
+    pub fn new(cfg: Cfg) -> Result<Self, failure::Error> {
+        cfg.check_format()?;
+        Ok(Self { cfg })
     }
 }
 
@@ -34,12 +39,23 @@ pub enum Timezone {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
 #[serde(rename_all = "snake_case")]
 pub struct Cfg {
     pub timezone: Timezone,
     pub format: String,
 }
 
+impl Cfg {
+    /// chrono only notices a broken format when it's used, then it panics
+    pub fn check_format(&self) -> Result<(), failure::Error> {
+        if StrftimeItems::new(&self.format).any(|item| item == Item::Error) {
+            return Err(format_err!("Invalid strftime format {:?}", self.format));
+        }
+        Ok(())
+    }
+}
+
 impl Default for Cfg {
     fn default() -> Self {
         Self {
@@ -3,6 +3,8 @@ use serde_derive::{Deserialize, Serialize};
 
 use crate::output::Output;
 
+const KEYS: &[&str] = &["full", "used", "percent_used"];
+
 pub struct Widget {
     format: FormatString,
     fmt_map: FormatMap,
@@ -16,10 +18,7 @@ impl Widget {
 
         Ok(Self {
             fmt_map,
-            format: FormatString::parse_with_allowed_keys(
-                &cfg.format,
-                &["full", "used", "percent_used"],
-            )?,
+            format: FormatString::parse_with_allowed_keys(&cfg.format, KEYS)?,
         })
     }
 }
@@ -87,16 +86,27 @@ impl super::Widget for Widget {
             mem_info.used as f64 / mem_info.total as f64 * 100.,
         );
 
-        sink.write(format_args!("{}", self.format.fmt(&self.fmt_map)?));
+        sink.write_format(self.format.fmt(&self.fmt_map)?);
         Ok(())
     }
+
+    fn values(&self) -> Option<&FormatMap> {
+        Some(&self.fmt_map)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct Cfg {
     format: String,
 }
 
+impl Cfg {
+    pub fn formats(&self) -> super::Formats<'_> {
+        vec![("format", &self.format, KEYS)]
+    }
+}
+
 impl Default for Cfg {
     fn default() -> Self {
         Self {
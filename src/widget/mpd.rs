@@ -1,6 +1,6 @@
 mod conn;
 
-use std::{cell::RefCell, time::Duration};
+use std::{cell::RefCell, os::unix::io::AsRawFd, time::Duration};
 
 use failure::format_err;
 use formatter::{FormatMap, FormatString};
@@ -12,6 +12,8 @@ use crate::{
     widget,
 };
 
+const RUNNING_KEYS: &[&str] = &["artist", "title", "path", "elapsed", "duration"];
+
 pub struct Widget {
     conn: Option<MpdConnection>,
     state: RefCell<MpdState>,
@@ -25,7 +27,6 @@ pub struct Widget {
 
 impl Widget {
     pub fn new(cfg: Cfg) -> Result<Self, failure::Error> {
-        let running_allowed_keys = ["artist", "title", "path", "elapsed", "duration"];
         Ok(Self {
             conn: match MpdConnection::connect(&cfg.endpoint) {
                 Ok(conn) => Some(conn),
@@ -36,12 +37,9 @@ impl Widget {
             },
             format_running: FormatString::parse_with_allowed_keys(
                 &cfg.format_running,
-                &running_allowed_keys,
-            )?,
-            format_paused: FormatString::parse_with_allowed_keys(
-                &cfg.format_paused,
-                &running_allowed_keys,
+                RUNNING_KEYS,
             )?,
+            format_paused: FormatString::parse_with_allowed_keys(&cfg.format_paused, RUNNING_KEYS)?,
             format_stopped: FormatString::parse_with_allowed_keys(&cfg.format_stopped, &[""])?,
             format_down: FormatString::parse_with_allowed_keys(&cfg.format_down, &[""])?,
             state: RefCell::new(MpdState::default()),
@@ -51,7 +49,55 @@ impl Widget {
     }
 }
 
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+
+struct Wakeup {
+    endpoint: String,
+}
+
+impl widget::Wakeup for Wakeup {
+    fn run(
+        self: Box<Self>,
+        cancel: &widget::Cancel,
+        wake: &mut dyn FnMut() -> bool,
+    ) -> Result<(), failure::Error> {
+        loop {
+            // needs its own connection because idle blocks it
+            let mut conn = match MpdConnection::connect(&self.endpoint) {
+                Ok(conn) => conn,
+                Err(e @ conn::Error::NotMpd) => return Err(e.into()),
+                Err(_) => {
+                    if !cancel.sleep(RECONNECT_INTERVAL)? {
+                        return Ok(());
+                    }
+                    continue;
+                }
+            };
+            // mpd is (back) up
+            if !wake() {
+                return Ok(());
+            }
+            while conn.start_idle("player").is_ok() {
+                if !cancel.wait(conn.as_raw_fd())? {
+                    return Ok(());
+                }
+                if conn.finish_idle().is_err() {
+                    break;
+                }
+                if !wake() {
+                    return Ok(());
+                }
+            }
+            // mpd went away
+            if !wake() {
+                return Ok(());
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Cfg {
     pub format_running: String,
     pub format_paused: String,
@@ -60,6 +106,17 @@ pub struct Cfg {
     pub endpoint: String,
 }
 
+impl Cfg {
+    pub fn formats(&self) -> widget::Formats<'_> {
+        vec![
+            ("format_running", &self.format_running, RUNNING_KEYS),
+            ("format_paused", &self.format_paused, RUNNING_KEYS),
+            ("format_stopped", &self.format_stopped, &[""]),
+            ("format_down", &self.format_down, &[""]),
+        ]
+    }
+}
+
 impl Default for Cfg {
     fn default() -> Self {
         Self {
@@ -104,9 +161,9 @@ impl widget::Widget for Widget {
                         &self.format_running
                     };
 
-                    sink.write(format_args!("{}", fmt.fmt(&self.fmt_map)?));
+                    sink.write_format(fmt.fmt(&self.fmt_map)?);
                 } else {
-                    sink.write(format_args!("{}", self.format_stopped.fmt(&self.fmt_map)?))
+                    sink.write_format(self.format_stopped.fmt(&self.fmt_map)?)
                 }
             }
 
@@ -141,12 +198,15 @@ impl widget::Widget for Widget {
                 if let Ok(conn) = MpdConnection::connect(&self.endpoint) {
                     self.conn = Some(conn);
                 }
-                sink.write_colored(
-                    Color::Bad,
-                    format_args!("{}", self.format_down.fmt(&self.fmt_map)?),
-                )
+                sink.write_format_colored(Color::Bad, self.format_down.fmt(&self.fmt_map)?)
             }
         }
         Ok(())
     }
+
+    fn wakeup(&self) -> Option<Box<dyn widget::Wakeup>> {
+        Some(Box::new(Wakeup {
+            endpoint: self.endpoint.clone(),
+        }))
+    }
 }
@@ -1,6 +1,7 @@
 use std::{
     io::{self, prelude::*, BufReader},
     net::{TcpStream, ToSocketAddrs},
+    os::unix::io::{AsRawFd, RawFd},
     str,
     time::Duration,
 };
@@ -121,13 +122,36 @@ impl MpdConnection {
         Ok(())
     }
 
-    fn send_command_with<F>(&mut self, cmd: &str, mut f: F) -> Result<(), Error>
+    /// Asks mpd to report the next change in one of the space separated subsystems, the
+    /// connection becomes readable once it does
+    pub fn start_idle(&mut self, subsystems: &str) -> Result<(), Error> {
+        self.sock
+            .get_mut()
+            .write_all(format!("idle {}\n", subsystems).as_bytes())?;
+        Ok(())
+    }
+
+    /// Reads the reply to `start_idle`, blocks until there is one
+    pub fn finish_idle(&mut self) -> Result<(), Error> {
+        self.sock.get_mut().set_read_timeout(None)?;
+        let res = self.read_response(|_, _| Some(()));
+        self.sock.get_mut().set_read_timeout(Some(READ_TIMEOUT))?;
+        res
+    }
+
+    fn send_command_with<F>(&mut self, cmd: &str, f: F) -> Result<(), Error>
     where
         F: FnMut(&str, &str) -> Option<()>,
     {
         assert!(cmd.ends_with('\n'));
         self.sock.get_mut().write_all(cmd.as_bytes())?;
+        self.read_response(f)
+    }
 
+    fn read_response<F>(&mut self, mut f: F) -> Result<(), Error>
+    where
+        F: FnMut(&str, &str) -> Option<()>,
+    {
         loop {
             self.buf.clear();
             self.sock.read_until(b'\n', &mut self.buf)?;
@@ -150,3 +174,9 @@ impl MpdConnection {
         Ok(())
     }
 }
+
+impl AsRawFd for MpdConnection {
+    fn as_raw_fd(&self) -> RawFd {
+        self.sock.get_ref().as_raw_fd()
+    }
+}
@@ -25,6 +25,8 @@ use crate::{
     widget,
 };
 
+const KEYS: &[&str] = &["ipv4", "ipv6", "if"];
+
 pub struct Widget {
     cache: HashMap<String, IfInfo>,
     sock: unix::InetStreamSock,
@@ -38,10 +40,7 @@ pub struct Widget {
 impl Widget {
     pub fn new(cfg: Cfg) -> Result<Self, failure::Error> {
         Ok(Self {
-            format_up: FormatString::parse_with_allowed_keys(
-                &cfg.format_up,
-                &["ipv4", "ipv6", "if"],
-            )?,
+            format_up: FormatString::parse_with_allowed_keys(&cfg.format_up, KEYS)?,
             format_down: FormatString::parse_with_allowed_keys(&cfg.format_down, &[""])?,
             interface: cfg.interface,
             cache: HashMap::new(),
@@ -118,10 +117,15 @@ impl widget::Widget for Widget {
             &self.format_down
         };
 
-        sink.write_colored(color, format_args!("{}", format.fmt(&self.fmt_map)?));
+        sink.write_format_colored(color, format.fmt(&self.fmt_map)?);
 
         Ok(())
     }
+
+    #[cfg(target_os = "linux")]
+    fn wakeup(&self) -> Option<Box<dyn widget::Wakeup>> {
+        Some(Box::new(linux::Wakeup))
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -156,6 +160,7 @@ pub struct InterfaceBlacklist(HashSet<String>);
 #[serde(rename_all = "snake_case")]
 pub enum Interface {
     Dynamic {
+        #[serde(default = "InterfaceBlacklist::new")]
         #[serde(deserialize_with = "deserialize_blacklist")]
         #[serde(serialize_with = "serialize_blacklist")]
         blacklist: InterfaceBlacklist,
@@ -166,12 +171,22 @@ pub enum Interface {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Cfg {
     format_up: String,
     format_down: String,
     interface: Interface,
 }
 
+impl Cfg {
+    pub fn formats(&self) -> widget::Formats<'_> {
+        vec![
+            ("format_up", &self.format_up, KEYS),
+            ("format_down", &self.format_down, &[""]),
+        ]
+    }
+}
+
 impl Default for Cfg {
     fn default() -> Self {
         Self {
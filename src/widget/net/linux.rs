@@ -1,9 +1,16 @@
-use std::{mem, ptr};
+use std::{mem, os::unix::io::RawFd, ptr};
 
 use linux_wireless::{iwreq, IFNAMSIZ, SIOCGIWNAME};
-use nix::{convert_ioctl_res, ioctl_read_bad};
+use nix::{
+    convert_ioctl_res,
+    errno::Errno,
+    ioctl_read_bad,
+    sys::socket::{bind, recv, socket, AddressFamily, MsgFlags, SockAddr, SockFlag, SockType},
+    unistd::close,
+};
 
 use super::unix::InetStreamSock;
+use crate::widget;
 
 ioctl_read_bad!(siocgiwname, SIOCGIWNAME, iwreq);
 
@@ -21,6 +28,55 @@ pub fn is_wireless_if(sock: InetStreamSock, if_: &str) -> bool {
     }
 }
 
+// multicast groups from linux/rtnetlink.h
+const RTMGRP_LINK: u32 = 0x1;
+const RTMGRP_IPV4_IFADDR: u32 = 0x10;
+const RTMGRP_IPV6_IFADDR: u32 = 0x100;
+
+struct NetlinkSock(RawFd);
+
+impl Drop for NetlinkSock {
+    fn drop(&mut self) {
+        let _ = close(self.0);
+    }
+}
+
+/// Wakes up when links go up or down or addresses change
+pub struct Wakeup;
+
+impl widget::Wakeup for Wakeup {
+    fn run(
+        self: Box<Self>,
+        cancel: &widget::Cancel,
+        wake: &mut dyn FnMut() -> bool,
+    ) -> Result<(), failure::Error> {
+        // protocol 0 is NETLINK_ROUTE
+        let sock = NetlinkSock(socket(
+            AddressFamily::Netlink,
+            SockType::Raw,
+            SockFlag::SOCK_CLOEXEC,
+            None,
+        )?);
+        bind(
+            sock.0,
+            &SockAddr::new_netlink(0, RTMGRP_LINK | RTMGRP_IPV4_IFADDR | RTMGRP_IPV6_IFADDR),
+        )?;
+        // only the fact that something changed is interesting, getifaddrs does the rest
+        let mut buf = [0u8; 4096];
+        while cancel.wait(sock.0)? {
+            match recv(sock.0, &mut buf, MsgFlags::empty()) {
+                // messages got dropped because the buffer was full, something changed anyway
+                Ok(_) | Err(nix::Error::Sys(Errno::ENOBUFS)) => {}
+                Err(e) => return Err(e.into()),
+            }
+            if !wake() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -8,6 +8,8 @@ use serde_derive::{Deserialize, Serialize};
 use self::linux::Sensor;
 use crate::output::Output;
 
+const KEYS: &[&str] = &["temp", "temp_crit", "temp_max"];
+
 pub struct Widget {
     fmt_map: FormatMap,
     format: FormatString,
@@ -27,7 +29,7 @@ impl Widget {
             sensor,
             fmt_map: FormatMap::new(),
             unit: cfg.unit,
-            format: FormatString::parse_with_allowed_keys(&cfg.format, &["temp"])?,
+            format: FormatString::parse_with_allowed_keys(&cfg.format, KEYS)?,
         })
     }
 }
@@ -35,19 +37,23 @@ impl Widget {
 impl super::Widget for Widget {
     fn run(&mut self, sink: &mut dyn Output) -> Result<(), failure::Error> {
         let temp = self.sensor.get_temp()?;
-        self.fmt_map.insert(
-            "temp",
-            match self.unit {
-                Unit::Celsius => temp.0,
-                Unit::Kelvin => Kelvin::from(temp).as_f64(),
-                Unit::Fahrenheit => Fahrenheit::from(temp).as_f64(),
-            },
-        );
+        self.fmt_map.insert("temp", self.unit.convert(temp));
+        // only known for some sensors
+        if let Some(crit) = self.sensor.temp_crit() {
+            self.fmt_map.insert("temp_crit", self.unit.convert(crit));
+        }
+        if let Some(max) = self.sensor.temp_max() {
+            self.fmt_map.insert("temp_max", self.unit.convert(max));
+        }
 
-        sink.write(format_args!("{}", self.format.fmt(&self.fmt_map)?));
+        sink.write_format(self.format.fmt(&self.fmt_map)?);
 
         Ok(())
     }
+
+    fn values(&self) -> Option<&FormatMap> {
+        Some(&self.fmt_map)
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -67,7 +73,18 @@ pub enum Unit {
     Fahrenheit,
 }
 
+impl Unit {
+    fn convert(self, temp: Celsius) -> f64 {
+        match self {
+            Unit::Celsius => temp.0,
+            Unit::Kelvin => Kelvin::from(temp).as_f64(),
+            Unit::Fahrenheit => Fahrenheit::from(temp).as_f64(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 #[serde(rename_all = "snake_case")]
 pub struct Cfg {
     unit: Unit,
@@ -75,6 +92,12 @@ pub struct Cfg {
     dev: Device,
 }
 
+impl Cfg {
+    pub fn formats(&self) -> super::Formats<'_> {
+        vec![("format", &self.format, KEYS)]
+    }
+}
+
 impl Default for Cfg {
     fn default() -> Self {
         Self {
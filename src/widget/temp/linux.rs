@@ -25,6 +25,9 @@ pub struct Sensor {
     temp_max: Option<Celsius>,
 }
 
+// the chip lives in the global state of libsensors which is never cleaned up
+unsafe impl Send for Sensor {}
+
 #[derive(Copy, Clone, Debug)]
 struct SubfeatureId(i32);
 
@@ -150,6 +153,14 @@ impl Sensor {
         Self::find(&allowed)
     }
 
+    pub fn temp_crit(&self) -> Option<Celsius> {
+        self.temp_crit
+    }
+
+    pub fn temp_max(&self) -> Option<Celsius> {
+        self.temp_max
+    }
+
     #[inline]
     pub fn get_temp(&self) -> Result<Celsius, failure::Error> {
         subfeature_get_value(self.chip, self.temp_id)
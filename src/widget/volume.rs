@@ -4,7 +4,7 @@ pub mod alsa;
 use formatter::{FormatMap, FormatString};
 use serde_derive::{Deserialize, Serialize};
 
-use crate::output::{Color, Output};
+use crate::output::{ClickEvent, Color, Output};
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "snake_case")]
@@ -19,6 +19,8 @@ struct VolumeState {
     pub is_muted: bool,
 }
 
+const KEYS: &[&str] = &["volume"];
+
 pub struct Widget {
     fmt_map: FormatMap,
     format: FormatString,
@@ -26,16 +28,22 @@ pub struct Widget {
     mixer: Box<dyn Mixer>,
 }
 
-trait Mixer {
+trait Mixer: Send {
     fn get_volume_state(&mut self) -> Result<VolumeState, failure::Error>;
+
+    fn toggle_mute(&mut self) -> Result<(), failure::Error>;
+
+    fn wakeup(&self) -> Option<Box<dyn super::Wakeup>> {
+        None
+    }
 }
 
 impl Widget {
     pub fn new(cfg: Cfg) -> Result<Self, failure::Error> {
         Ok(Self {
             fmt_map: FormatMap::new(),
-            format: FormatString::parse_with_allowed_keys(&cfg.format, &["volume"])?,
-            format_muted: FormatString::parse_with_allowed_keys(&cfg.format_muted, &["volume"])?,
+            format: FormatString::parse_with_allowed_keys(&cfg.format, KEYS)?,
+            format_muted: FormatString::parse_with_allowed_keys(&cfg.format_muted, KEYS)?,
             mixer: match cfg.backend {
                 #[cfg(target_os = "linux")]
                 Backend::Alsa(cfg) => Box::new(alsa::AlsaMixer::new(cfg)?),
@@ -51,25 +59,48 @@ impl super::Widget for Widget {
         self.fmt_map.insert("volume", state.volume);
 
         if state.is_muted {
-            sink.write_colored(
-                Color::Mediocre,
-                format_args!("{}", self.format_muted.fmt(&self.fmt_map)?),
-            );
+            sink.write_format_colored(Color::Mediocre, self.format_muted.fmt(&self.fmt_map)?);
         } else {
-            sink.write(format_args!("{}", self.format.fmt(&self.fmt_map)?));
+            sink.write_format(self.format.fmt(&self.fmt_map)?);
         }
 
         Ok(())
     }
+
+    /// A left click mutes or unmutes
+    fn on_click(&mut self, ev: &ClickEvent) -> Result<(), failure::Error> {
+        if ev.button == 1 {
+            self.mixer.toggle_mute()?;
+        }
+        Ok(())
+    }
+
+    fn wakeup(&self) -> Option<Box<dyn super::Wakeup>> {
+        self.mixer.wakeup()
+    }
+
+    fn values(&self) -> Option<&FormatMap> {
+        Some(&self.fmt_map)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct Cfg {
     pub format: String,
     pub format_muted: String,
     pub backend: Backend,
 }
 
+impl Cfg {
+    pub fn formats(&self) -> super::Formats<'_> {
+        vec![
+            ("format", &self.format, KEYS),
+            ("format_muted", &self.format_muted, KEYS),
+        ]
+    }
+}
+
 impl Default for Cfg {
     fn default() -> Self {
         Self {
@@ -80,3 +111,52 @@ impl Default for Cfg {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::widget::Widget as _;
+
+    struct Fake(bool);
+
+    impl Mixer for Fake {
+        fn get_volume_state(&mut self) -> Result<VolumeState, failure::Error> {
+            Ok(VolumeState {
+                volume: 50.0,
+                is_muted: self.0,
+            })
+        }
+
+        fn toggle_mute(&mut self) -> Result<(), failure::Error> {
+            self.0 = !self.0;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn left_click_toggles_mute() {
+        let cfg = Cfg::default();
+        let mut widget = Widget {
+            fmt_map: FormatMap::new(),
+            format: FormatString::parse(&cfg.format).unwrap(),
+            format_muted: FormatString::parse(&cfg.format_muted).unwrap(),
+            mixer: Box::new(Fake(false)),
+        };
+        let click = |button| {
+            ClickEvent::from_line(&format!(
+                r#"{{"name":"volume","instance":"0","button":{}}}"#,
+                button
+            ))
+            .unwrap()
+            .unwrap()
+        };
+        let muted = |widget: &mut Widget| widget.mixer.get_volume_state().unwrap().is_muted;
+
+        widget.on_click(&click(3)).unwrap();
+        assert!(!muted(&mut widget));
+        widget.on_click(&click(1)).unwrap();
+        assert!(muted(&mut widget));
+        widget.on_click(&click(1)).unwrap();
+        assert!(!muted(&mut widget));
+    }
+}
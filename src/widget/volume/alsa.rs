@@ -1,11 +1,14 @@
 // FIXME: this entire thing
 use alsa::{
     mixer::{Selem, SelemChannelId, SelemId},
-    Mixer,
+    poll::PollDescriptors,
+    Ctl, Mixer,
 };
 use failure::format_err;
 use serde_derive::{Deserialize, Serialize};
 
+use crate::widget;
+
 struct AlsaConn {
     vol_range: (i64, i64),
     has_playback_switch: bool,
@@ -35,6 +38,54 @@ impl super::Mixer for AlsaMixer {
         let is_muted = self.conn.is_muted(&selem, self.cfg.channel_id)?;
         Ok(super::VolumeState { volume, is_muted })
     }
+
+    fn toggle_mute(&mut self) -> Result<(), failure::Error> {
+        if !self.conn.has_playback_switch {
+            return Err(format_err!("{} can't be muted", self.cfg.device));
+        }
+        let mixer = connect_mixer(&self.cfg.mixer)?;
+        let selem = self
+            .conn
+            .get_selem(&mixer, &self.cfg.device, self.cfg.mixer_index)?;
+        let on = selem.get_playback_switch(self.cfg.channel_id)?;
+        selem.set_playback_switch_all(if on == 0 { 1 } else { 0 })?;
+        Ok(())
+    }
+
+    fn wakeup(&self) -> Option<Box<dyn widget::Wakeup>> {
+        Some(Box::new(Wakeup {
+            mixer: self.cfg.mixer.clone(),
+        }))
+    }
+}
+
+struct Wakeup {
+    mixer: String,
+}
+
+impl widget::Wakeup for Wakeup {
+    fn run(
+        self: Box<Self>,
+        cancel: &widget::Cancel,
+        wake: &mut dyn FnMut() -> bool,
+    ) -> Result<(), failure::Error> {
+        let ctl = Ctl::new(&self.mixer, false)
+            .map_err(|e| format_err!("Can't open control {}: {}", self.mixer, e))?;
+        ctl.subscribe_events(true)?;
+        let fd = ctl
+            .get()?
+            .first()
+            .map(|pollfd| pollfd.fd)
+            .ok_or_else(|| format_err!("Control {} has nothing to poll", self.mixer))?;
+        // readable once any control of the card changes
+        while cancel.wait(fd)? {
+            ctl.read()?;
+            if !wake() {
+                break;
+            }
+        }
+        Ok(())
+    }
 }
 
 fn connect_mixer(mixer_name: &str) -> Result<Mixer, failure::Error> {
@@ -122,6 +173,7 @@ enum SelemChannelIdDef {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct Cfg {
     pub mixer: String,
     pub device: String,